@@ -0,0 +1,386 @@
+pub use super::*;
+use iyes_loopless::prelude::*;
+
+mod save;
+pub use save::*;
+
+// Same as the Wall value of the Collisions layer in LDtk
+pub const WALL_COLOR: &str = "151C25";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EditorTool {
+    Player,
+    #[default]
+    Box,
+    Wall,
+    // Dragged out as a rectangle
+    TimeMachine,
+    // Opens or closes the outside of a time machine part
+    Opening
+}
+
+impl EditorTool {
+    pub const ALL: [(KeyCode, EditorTool); 5] = [
+        (KeyCode::Key1, EditorTool::Player),
+        (KeyCode::Key2, EditorTool::Box),
+        (KeyCode::Key3, EditorTool::Wall),
+        (KeyCode::Key4, EditorTool::TimeMachine),
+        (KeyCode::Key5, EditorTool::Opening)
+    ];
+}
+
+// F2 goes between the editor and play-testing what's in it
+#[derive(Resource, Default)]
+pub struct Editor {
+    pub tool: EditorTool,
+    // Where the time machine being dragged out started
+    pub drag_start: Option<(usize, usize)>,
+    // The level as it is in the editor, play-testing doesn't change it. It starts
+    // out as the level right after setup
+    pub level: Option<Grid>
+}
+
+#[derive(Component)]
+pub struct EditorText;
+
+// Walls added in the editor, the ones from LDtk are drawn by the Collisions layer
+#[derive(Component)]
+pub struct EditorWall;
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Editor::default())
+            // Filled in by register_behaviour, which can come before or after this plugin
+            .init_resource::<RegisteredBehaviours>()
+            .add_startup_system(spawn_editor_text)
+            .add_system(forget_level_start)
+            .add_system(
+                remember_level_start
+                .run_if(level_setup_completed)
+                .after("end_level_setup")
+            )
+            .add_system(toggle_editor)
+            .add_system_set(
+                SystemSet::on_enter(AppState::Editor)
+                .with_system(enter_editor)
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Editor)
+                .with_system(exit_editor)
+            )
+            .add_system_set(
+                ConditionSet::new()
+                .run_if(in_editor)
+                .label("edit level")
+                .with_system(select_tool)
+                .with_system(place_on_click)
+                .with_system(remove_on_click)
+                .with_system(save_on_key)
+                .into()
+            )
+            .add_system(
+                update_to_grid
+                .run_if(in_editor)
+                .run_if(grid_changed)
+                .after("edit level")
+            )
+            .add_system(
+                update_walls
+                .run_if(in_editor)
+                .run_if(grid_changed)
+                .after("edit level")
+            )
+            .add_system(update_editor_text)
+        ;
+    }
+}
+
+pub fn in_editor(app_state: Res<State<AppState>>) -> bool {
+    app_state.current() == &AppState::Editor
+}
+
+fn grid_changed(grid: Res<Grid>) -> bool {
+    grid.is_changed()
+}
+
+pub fn spawn_editor_text(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.spawn((
+        TextBundle::from_section("", hud_text_style(&asset_server, Color::YELLOW))
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(40.0),
+                    left: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        EditorText
+    ))
+    .insert(Visibility { is_visible: false });
+}
+
+// A reloaded level is the one to edit from then on
+fn forget_level_start(
+    mut level_events: EventReader<LevelEvent>,
+    mut editor: ResMut<Editor>
+) {
+    for level_event in level_events.iter() {
+        if let LevelEvent::Spawned(_) = level_event {
+            editor.level = None;
+        }
+    }
+}
+
+fn remember_level_start(grid: Res<Grid>, mut editor: ResMut<Editor>) {
+    if editor.level.is_none() {
+        editor.level = Some(grid.clone());
+    }
+}
+
+fn toggle_editor(
+    input: Res<Input<KeyCode>>,
+    grid: Res<Grid>,
+    mut app_state: ResMut<State<AppState>>
+) {
+    if !input.just_pressed(KeyCode::F2) { return; }
+
+    let next_state = match app_state.current() {
+        AppState::InGame => AppState::Editor,
+        AppState::Editor if grid.get_entity_from_id("Player", 0).is_none() => {
+            warn!("Can't play-test a level without a player");
+            return;
+        },
+        AppState::Editor => AppState::InGame,
+        _ => return
+    };
+
+    if let Err(error) = app_state.set(next_state) {
+        warn!("Couldn't switch to or from the editor: {}", error);
+    }
+}
+
+// Both ways start the level over, so neither the moves nor the time spent
+// in the editor count towards the result
+fn reset_level_progress(
+    ticks: &mut Ticks,
+    input_queue: &mut InputQueue,
+    level_stats: &mut LevelStats
+) {
+    ticks.0 = 0;
+    input_queue.0.clear();
+    *level_stats = LevelStats::default();
+}
+
+fn enter_editor(
+    mut grid: ResMut<Grid>,
+    mut editor: ResMut<Editor>,
+    mut ticks: ResMut<Ticks>,
+    mut input_queue: ResMut<InputQueue>,
+    mut level_stats: ResMut<LevelStats>
+) {
+    if let Some(level) = &editor.level {
+        *grid = level.clone();
+    }
+
+    editor.drag_start = None;
+    reset_level_progress(&mut ticks, &mut input_queue, &mut level_stats);
+}
+
+fn exit_editor(
+    grid: Res<Grid>,
+    mut editor: ResMut<Editor>,
+    mut ticks: ResMut<Ticks>,
+    mut input_queue: ResMut<InputQueue>,
+    mut level_stats: ResMut<LevelStats>
+) {
+    editor.level = Some(grid.clone());
+    reset_level_progress(&mut ticks, &mut input_queue, &mut level_stats);
+}
+
+fn select_tool(input: Res<Input<KeyCode>>, mut editor: ResMut<Editor>) {
+    for (key, tool) in EditorTool::ALL {
+        if input.just_pressed(key) {
+            editor.tool = tool;
+            editor.drag_start = None;
+        }
+    }
+}
+
+// The cursor's cell if it's inside of the level
+fn level_cell(windows: &Windows, level_window_info: &LevelWindowInfo, grid: &Grid) -> Option<(usize, usize)> {
+    cursor_cell(windows, level_window_info).filter(|(x, y)| *x < grid.width() && *y < grid.height())
+}
+
+fn place_on_click(
+    buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    level_window_info: Res<LevelWindowInfo>,
+    mut editor: ResMut<Editor>,
+    mut grid: ResMut<Grid>
+) {
+    if editor.tool == EditorTool::TimeMachine {
+        if buttons.just_pressed(MouseButton::Left) {
+            editor.drag_start = level_cell(&windows, &level_window_info, &grid);
+        }
+
+        if let (true, Some(start)) = (buttons.just_released(MouseButton::Left), editor.drag_start) {
+            editor.drag_start = None;
+
+            let end = if let Some(end) = level_cell(&windows, &level_window_info, &grid) { end } else { return; };
+            let corner = (start.0.min(end.0), start.1.min(end.1));
+            let (width, height) = (start.0.abs_diff(end.0) + 1, start.1.abs_diff(end.1) + 1);
+
+            if let Err(refused) = grid.can_place_time_machine(corner, width, height) {
+                warn!("{}", refused);
+                return;
+            }
+
+            let id = grid.num_entities();
+            grid.add_time_machine(corner, width, height, id);
+            grid.update_plates();
+        }
+
+        return;
+    }
+
+    if !buttons.just_pressed(MouseButton::Left) { return; }
+
+    let (x, y) = if let Some(cell) = level_cell(&windows, &level_window_info, &grid) { cell } else { return; };
+
+    match editor.tool {
+        EditorTool::Player | EditorTool::Box if grid.is_free(x, y) => {
+            let (variant, id) = if editor.tool == EditorTool::Player {
+                // There's only ever one player, placing it moves it
+                if let Some(&(pos, _)) = grid.get_entity_from_id("Player", 0) {
+                    grid.remove_at(pos.0, pos.1);
+                }
+
+                ("Player", 0)
+            } else {
+                ("Box", grid.num_entities())
+            };
+
+            grid.add_entity_to_pos(x, y, &mut GridEntityInfo { variant, id, ..default() });
+        },
+        EditorTool::Wall if !grid.is_wall(x, y) && grid.grid_index(x, y) == 0 => grid.set_wall(x, y, true),
+        EditorTool::Opening => if !grid.toggle_opening(x, y) { return; },
+        _ => return
+    }
+
+    grid.update_plates();
+}
+
+// Takes away the innermost thing in the cell, then the wall
+fn remove_on_click(
+    buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    level_window_info: Res<LevelWindowInfo>,
+    mut grid: ResMut<Grid>
+) {
+    if !buttons.just_pressed(MouseButton::Right) { return; }
+
+    let (x, y) = if let Some(cell) = level_cell(&windows, &level_window_info, &grid) { cell } else { return; };
+
+    if grid.remove_at(x, y) || grid.is_wall(x, y) {
+        grid.set_wall(x, y, false);
+        grid.update_plates();
+    }
+}
+
+fn save_on_key(
+    input: Res<Input<KeyCode>>,
+    grid: Res<Grid>,
+    level_info: Res<LevelInfo>,
+    registered_behaviours: Res<RegisteredBehaviours>
+) {
+    let ctrl = input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+
+    if !(ctrl && input.just_pressed(KeyCode::S)) { return; }
+
+    match save_level(&grid, &level_info.identifier, &registered_behaviours.0) {
+        Ok(()) => info!("Saved {} to {}", level_info.identifier, LDTK_FILE),
+        Err(error) => error!("Couldn't save {}: {}", level_info.identifier, error)
+    }
+}
+
+// Wall tiles of removed walls get despawned, added walls get a sprite of their own
+fn update_walls(
+    mut commands: Commands,
+    grid: Res<Grid>,
+    ldtk_level: Query<Entity, With<Handle<LdtkLevel>>>,
+    wall_tiles_query: Query<(Entity, &GridCoords), With<IntGridCell>>,
+    editor_walls_query: Query<Entity, With<EditorWall>>
+) {
+    let level_entity = if let Ok(level_entity) = ldtk_level.get_single() { level_entity } else { return; };
+
+    for editor_wall in editor_walls_query.iter() {
+        commands.entity(editor_wall).despawn_recursive();
+    }
+
+    let mut drawn = vec![vec![false; grid.height()]; grid.width()];
+
+    for (wall_tile, coords) in wall_tiles_query.iter() {
+        let (x, y) = (coords.x as usize, coords.y as usize);
+
+        if x < grid.width() && y < grid.height() && grid.is_wall(x, y) {
+            drawn[x][y] = true;
+        } else {
+            commands.entity(wall_tile).despawn_recursive();
+        }
+    }
+
+    commands.entity(level_entity).with_children(|parent| {
+        for (x, column) in drawn.iter().enumerate() {
+            for (y, &wall_drawn) in column.iter().enumerate() {
+                if !grid.is_wall(x, y) || wall_drawn { continue; }
+
+                parent.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::hex(WALL_COLOR).unwrap(),
+                            custom_size: Some(Vec2::new(TILE_SIZE as f32, TILE_SIZE as f32)),
+                            ..default()
+                        },
+                        transform: Transform::from_xyz(
+                            (TILE_SIZE * x + TILE_SIZE / 2) as f32,
+                            (TILE_SIZE * y + TILE_SIZE / 2) as f32,
+                            0.0
+                        ),
+                        ..default()
+                    },
+                    EditorWall
+                ));
+            }
+        }
+    });
+}
+
+pub fn update_editor_text(
+    editor: Res<Editor>,
+    app_state: Res<State<AppState>>,
+    mut editor_text_query: Query<(&mut Text, &mut Visibility), With<EditorText>>
+) {
+    let (mut text, mut visibility) = if let Ok(editor_text) = editor_text_query.get_single_mut() { editor_text } else { return; };
+
+    visibility.is_visible = app_state.current() == &AppState::Editor;
+
+    if !(editor.is_changed() || app_state.is_changed()) { return; }
+
+    let tools = EditorTool::ALL.iter().enumerate().fold(String::new(), |string, (i, (_, tool))| {
+        let marker = if *tool == editor.tool { ">" } else { " " };
+
+        format!("{}{}{} {:?}  ", string, marker, i + 1, tool)
+    });
+
+    text.sections[0].value = format!(
+        "Editor: {}\nLeft click places, right click removes, F2 play-tests, Ctrl+S saves",
+        tools
+    );
+}
@@ -0,0 +1,345 @@
+use super::*;
+use serde::Serialize;
+use serde_json::{json, ser::PrettyFormatter, Serializer, Value};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+// What the editor places and the layer each goes in, other LDtk entities are left as they are
+const EDITED_ENTITIES: [(&str, &str); 3] = [
+    ("Player", "Interactables"),
+    ("Box", "Interactables"),
+    ("Time_machine_part", "Time_machines")
+];
+// Registered behaviours all go in with the players and boxes
+const CUSTOM_ENTITY_LAYER: &str = "Interactables";
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    // The level, a layer or a definition isn't in the LDtk file
+    Missing(String),
+    // The grid isn't the size of the level in the LDtk file
+    WrongSize {
+        grid: (usize, usize),
+        level: (usize, usize)
+    }
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "{}", error),
+            SaveError::Json(error) => write!(f, "invalid LDtk file: {}", error),
+            SaveError::Missing(what) => write!(f, "{} isn't in the LDtk file", what),
+            SaveError::WrongSize { grid, level } => {
+                write!(f, "the grid is {}x{} but the level is {}x{}", grid.0, grid.1, level.0, level.1)
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(error: std::io::Error) -> Self { SaveError::Io(error) }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(error: serde_json::Error) -> Self { SaveError::Json(error) }
+}
+
+// An entity instance for something in the grid, the cell goes up from the bottom like GridCoords
+struct PlacedEntity {
+    identifier: &'static str,
+    cell: (usize, usize),
+    fields: Vec<(&'static str, Value)>
+}
+
+fn placed_entities(grid: &Grid) -> Vec<PlacedEntity> {
+    let mut placed = Vec::new();
+
+    for (corner, grid_entity) in grid.entities_iter() {
+        match grid_entity {
+            GridEntity::Player { .. } => placed.push(PlacedEntity { identifier: "Player", cell: *corner, fields: Vec::new() }),
+            GridEntity::Box { .. } => placed.push(PlacedEntity { identifier: "Box", cell: *corner, fields: Vec::new() }),
+            GridEntity::TimeMachine { grid: parts, pushable, kind, .. } => {
+                for (i, j) in grid_entity.tm_cells() {
+                    // Every part gets its type so autotiling leaves the openings alone
+                    let mut fields = vec![("TimeMachineType", json!(format!("{:?}", parts[i][j].0)))];
+
+                    if *pushable {
+                        fields.push(("Pushable", json!(true)));
+                    }
+
+                    if let TimeMachineKind::Stasis { ticks } = kind {
+                        fields.push(("Kind", json!("Stasis")));
+                        fields.push(("StasisTicks", json!(ticks)));
+                    }
+
+                    placed.push(PlacedEntity {
+                        identifier: "Time_machine_part",
+                        cell: (corner.0 + i, corner.1 + j),
+                        fields
+                    });
+                }
+            },
+            GridEntity::Custom(custom) => placed.push(PlacedEntity {
+                identifier: custom.0.variant(),
+                cell: *corner,
+                fields: custom.0.to_field_instances()
+            }),
+            _ => {}
+        }
+    }
+
+    placed
+}
+
+fn layer_of(identifier: &str) -> &'static str {
+    EDITED_ENTITIES
+        .iter()
+        .find(|(edited, _)| *edited == identifier)
+        .map_or(CUSTOM_ENTITY_LAYER, |(_, layer)| layer)
+}
+
+// Writes the walls, players, boxes, time machines and registered behaviours of the grid into the
+// level in the LDtk file. Entities that are still in the same cell keep their iid, so references
+// to them still work
+pub fn save_level(grid: &Grid, identifier: &str, custom_identifiers: &[String]) -> Result<(), SaveError> {
+    let path = format!("assets/{}", LDTK_FILE);
+    let mut project: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    let defs = project["defs"].clone();
+
+    let level = project["levels"]
+        .as_array_mut()
+        .and_then(|levels| levels.iter_mut().find(|level| level["identifier"] == identifier))
+        .ok_or_else(|| SaveError::Missing(format!("Level {}", identifier)))?;
+
+    let layers = level["layerInstances"]
+        .as_array_mut()
+        .ok_or_else(|| SaveError::Missing(format!("The layers of {}", identifier)))?;
+
+    // (identifier, __grid, iid) of everything the grid replaces, from whichever layer it was in
+    let mut old_iids: Vec<(Value, Value, Value)> = Vec::new();
+
+    for layer in layers.iter_mut() {
+        if let Some(instances) = layer["entityInstances"].as_array_mut() {
+            instances.retain(|instance| {
+                let edited = EDITED_ENTITIES.iter().any(|(edited, _)| instance["__identifier"] == *edited)
+                    || custom_identifiers.iter().any(|custom| instance["__identifier"] == *custom);
+
+                if edited {
+                    old_iids.push((instance["__identifier"].clone(), instance["__grid"].clone(), instance["iid"].clone()));
+                }
+
+                !edited
+            });
+        }
+    }
+
+    let placed = placed_entities(grid);
+
+    for layer in layers.iter_mut() {
+        let layer_identifier = layer["__identifier"].as_str().unwrap_or_default().to_string();
+        let c_wid = layer["__cWid"].as_u64().unwrap_or(0) as usize;
+        let c_hei = layer["__cHei"].as_u64().unwrap_or(0) as usize;
+        let grid_size = layer["__gridSize"].as_u64().unwrap_or(TILE_SIZE as u64) as usize;
+
+        let written = layer_identifier == "Collisions"
+            || layer_identifier == CUSTOM_ENTITY_LAYER
+            || EDITED_ENTITIES.iter().any(|(_, edited_layer)| *edited_layer == layer_identifier);
+
+        if !written { continue; }
+
+        if (c_wid, c_hei) != (grid.width(), grid.height()) {
+            return Err(SaveError::WrongSize { grid: (grid.width(), grid.height()), level: (c_wid, c_hei) });
+        }
+
+        // LDtk stores the rows top to bottom, 1 is a wall
+        if layer_identifier == "Collisions" {
+            layer["intGridCsv"] = (0..c_hei).rev()
+                .flat_map(|y| (0..c_wid).map(move |x| (x, y)))
+                .map(|(x, y)| if grid.is_wall(x, y) { 1 } else { 0 })
+                .collect();
+
+            continue;
+        }
+
+        let mut new_instances = Vec::new();
+
+        for placed_entity in placed.iter() {
+            if layer_of(placed_entity.identifier) == layer_identifier {
+                new_instances.push(entity_instance(placed_entity, c_hei, grid_size, &defs, &old_iids)?);
+            }
+        }
+
+        if let Some(instances) = layer["entityInstances"].as_array_mut() {
+            instances.extend(new_instances);
+        }
+    }
+
+    warn_dangling_refs(level);
+
+    // LDtk indents with tabs
+    let mut contents = Vec::new();
+    project.serialize(&mut Serializer::with_formatter(&mut contents, PrettyFormatter::with_indent(b"\t")))?;
+    std::fs::write(&path, contents)?;
+
+    Ok(())
+}
+
+// Entity refs like ActiveIndicator.Machine point at an iid, which is gone once the entity it
+// belonged to got moved or removed in the editor. LDtk shows them as broken until they're set again
+fn warn_dangling_refs(level: &Value) {
+    let instances = || level["layerInstances"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|layer| layer["entityInstances"].as_array().into_iter().flatten());
+
+    let iids: Vec<&Value> = instances().map(|instance| &instance["iid"]).collect();
+
+    for instance in instances() {
+        for field in instance["fieldInstances"].as_array().into_iter().flatten() {
+            let refs = match &field["__value"] {
+                Value::Array(values) => values.iter().collect(),
+                value => vec![value]
+            };
+
+            // Refs to other levels can't be checked from here
+            let dangling = refs
+                .into_iter()
+                .filter(|entity_ref| entity_ref["levelIid"] == level["iid"] && !iids.contains(&&entity_ref["entityIid"]));
+
+            for entity_ref in dangling {
+                warn!(
+                    "{}.{} at {} points at {}, which isn't in {} anymore",
+                    instance["__identifier"],
+                    field["__identifier"],
+                    instance["__grid"],
+                    entity_ref["entityIid"],
+                    level["identifier"]
+                );
+            }
+        }
+    }
+}
+
+fn find_identifier<'a>(array: &'a Value, identifier: &str) -> Option<&'a Value> {
+    array.as_array()?.iter().find(|value| value["identifier"] == identifier)
+}
+
+fn entity_instance(
+    placed_entity: &PlacedEntity,
+    c_hei: usize,
+    grid_size: usize,
+    defs: &Value,
+    old_iids: &[(Value, Value, Value)]
+) -> Result<Value, SaveError> {
+    let def = find_identifier(&defs["entities"], placed_entity.identifier)
+        .ok_or_else(|| SaveError::Missing(format!("Entity {}", placed_entity.identifier)))?;
+
+    let (x, y) = (placed_entity.cell.0, c_hei - 1 - placed_entity.cell.1);
+    let grid = json!([x, y]);
+
+    let iid = old_iids
+        .iter()
+        .find(|(identifier, old_grid, _)| *identifier == placed_entity.identifier && *old_grid == grid)
+        .map_or_else(|| json!(new_iid()), |(.., iid)| iid.clone());
+
+    let field_instances = placed_entity.fields
+        .iter()
+        .map(|(identifier, value)| field_instance(def, identifier, value.clone(), defs))
+        .collect::<Result<Vec<Value>, SaveError>>()?;
+
+    // Entities without a tile of their own are drawn with the tile of their enum field
+    let tile = if def["tileRect"].is_null() {
+        field_instances.iter().map(|field| &field["__tile"]).find(|tile| !tile.is_null()).cloned().unwrap_or(Value::Null)
+    } else {
+        def["tileRect"].clone()
+    };
+
+    let (width, height) = (def["width"].as_f64().unwrap_or(0.0), def["height"].as_f64().unwrap_or(0.0));
+    let (pivot_x, pivot_y) = (def["pivotX"].as_f64().unwrap_or(0.0), def["pivotY"].as_f64().unwrap_or(0.0));
+
+    Ok(json!({
+        "__identifier": placed_entity.identifier,
+        "__grid": grid,
+        "__pivot": [def["pivotX"], def["pivotY"]],
+        "__tags": def["tags"],
+        "__tile": tile,
+        "__smartColor": def["color"],
+        "iid": iid,
+        "width": def["width"],
+        "height": def["height"],
+        "defUid": def["uid"],
+        "px": [
+            (x * grid_size) as i64 + (pivot_x * width) as i64,
+            (y * grid_size) as i64 + (pivot_y * height) as i64
+        ],
+        "fieldInstances": field_instances
+    }))
+}
+
+fn field_instance(def: &Value, identifier: &str, value: Value, defs: &Value) -> Result<Value, SaveError> {
+    let field_def = find_identifier(&def["fieldDefs"], identifier)
+        .ok_or_else(|| SaveError::Missing(format!("Field {} of {}", identifier, def["identifier"])))?;
+
+    let editor_value_id = match value {
+        Value::Bool(_) => "V_Bool",
+        Value::Number(_) => "V_Int",
+        _ => "V_String"
+    };
+
+    // Enum values can have a tile, which LDtk keeps next to the value
+    let tile = field_def["__type"]
+        .as_str()
+        .and_then(|field_type| field_type.strip_prefix("LocalEnum."))
+        .and_then(|enum_identifier| find_identifier(&defs["enums"], enum_identifier))
+        .and_then(|enum_def| {
+            let tile_id = enum_def["values"].as_array()?.iter().find(|enum_value| enum_value["id"] == value)?["tileId"].as_u64()?;
+
+            tile_rect(defs, &enum_def["iconTilesetUid"], tile_id as usize)
+        })
+        .unwrap_or(Value::Null);
+
+    Ok(json!({
+        "__identifier": identifier,
+        "__value": value,
+        "__type": field_def["__type"],
+        "__tile": tile,
+        "defUid": field_def["uid"],
+        "realEditorValues": [{ "id": editor_value_id, "params": [value] }]
+    }))
+}
+
+fn tile_rect(defs: &Value, tileset_uid: &Value, tile_id: usize) -> Option<Value> {
+    let tileset = defs["tilesets"].as_array()?.iter().find(|tileset| &tileset["uid"] == tileset_uid)?;
+
+    let c_wid = tileset["__cWid"].as_u64()? as usize;
+    let grid_size = tileset["tileGridSize"].as_u64()? as usize;
+    let spacing = tileset["spacing"].as_u64().unwrap_or(0) as usize;
+    let padding = tileset["padding"].as_u64().unwrap_or(0) as usize;
+
+    Some(json!({
+        "tilesetUid": tileset_uid,
+        "x": padding + (tile_id % c_wid) * (grid_size + spacing),
+        "y": padding + (tile_id / c_wid) * (grid_size + spacing),
+        "w": grid_size,
+        "h": grid_size
+    }))
+}
+
+// Looks like the uuids LDtk makes, it only has to be unique in the file
+fn new_iid() -> String {
+    let random = || RandomState::new().build_hasher().finish();
+    let (high, low) = (random(), random());
+
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
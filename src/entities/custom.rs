@@ -0,0 +1,101 @@
+use super::*;
+use std::marker::PhantomData;
+
+// Everything a custom entity needs on the bevy side, whether it comes from
+// LDtk or gets spawned by update_to_grid after time travel
+#[derive(Bundle)]
+pub struct CustomEntityBundle {
+    pub custom: CustomEntity,
+    pub grid_entity: GridEntityInfo,
+    pub position: GridCoords,
+    #[bundle]
+    pub sprite_bundle: SpriteSheetBundle
+}
+
+impl CustomEntityBundle {
+    pub fn new(
+        custom: CustomEntity,
+        corner: (usize, usize),
+        text_atlases: &mut Assets<TextureAtlas>,
+        asset_server: &AssetServer
+    ) -> Self {
+        Self {
+            grid_entity: GridEntityInfo {
+                variant: custom.0.variant(),
+                id: custom.0.id(),
+                ..Default::default()
+            },
+            position: GridCoords::new(corner.0 as i32, corner.1 as i32),
+            sprite_bundle: SpriteSheetBundle { 
+                sprite: TextureAtlasSprite {
+                    index: custom.0.tile_index(),
+                    color: custom.0.color(),
+                    ..Default::default()
+                },
+                texture_atlas: tileset_atlas(text_atlases, asset_server),
+                transform: Transform::from_xyz(
+                    (TILE_SIZE * corner.0 + TILE_SIZE / 2) as f32,
+                    (TILE_SIZE * corner.1 + TILE_SIZE / 2) as f32, 
+                    0.0
+                ),
+                ..Default::default() 
+            },
+            custom
+        }
+    }
+}
+
+// Lets systems query the bevy entities of one custom type
+#[derive(Component)]
+pub struct Behaviour<B: EntityBehaviour>(PhantomData<B>);
+
+#[derive(Bundle)]
+pub struct BehaviourBundle<B: EntityBehaviour> {
+    #[bundle]
+    pub custom: CustomEntityBundle,
+    pub behaviour: Behaviour<B>
+}
+
+impl<B: EntityBehaviour> LdtkEntity for BehaviourBundle<B> {
+    fn bundle_entity(
+        entity_instance: &EntityInstance,
+        layer_instance: &LayerInstance,
+        _: Option<&Handle<Image>>,
+        _: Option<&bevy_ecs_ldtk::ldtk::TilesetDefinition>,
+        asset_server: &AssetServer,
+        texture_atlases: &mut Assets<TextureAtlas>
+    ) -> Self {
+        // LDtk counts rows from the top, GridCoords from the bottom
+        let corner = (
+            entity_instance.grid.x as usize,
+            (layer_instance.c_hei - 1 - entity_instance.grid.y) as usize
+        );
+
+        Self {
+            custom: CustomEntityBundle::new(
+                CustomEntity(std::boxed::Box::new(B::from_instance(entity_instance))),
+                corner,
+                texture_atlases,
+                asset_server
+            ),
+            behaviour: Behaviour(PhantomData)
+        }
+    }
+}
+
+// The LDtk identifiers of every registered behaviour, saving the editor's level replaces these
+#[derive(Resource, Default)]
+pub struct RegisteredBehaviours(pub Vec<String>);
+
+pub trait RegisterBehaviour {
+    fn register_behaviour<B: EntityBehaviour>(&mut self, identifier: &str) -> &mut Self;
+}
+
+impl RegisterBehaviour for App {
+    fn register_behaviour<B: EntityBehaviour>(&mut self, identifier: &str) -> &mut Self {
+        self.init_resource::<RegisteredBehaviours>();
+        self.world.resource_mut::<RegisteredBehaviours>().0.push(identifier.to_string());
+
+        self.register_ldtk_entity::<BehaviourBundle<B>>(identifier)
+    }
+}
@@ -0,0 +1,163 @@
+use super::*;
+
+// Why a field of an LDtk entity or level couldn't be read
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldError {
+    WrongType {
+        identifier: String,
+        expected: &'static str
+    },
+    InvalidValue {
+        identifier: String,
+        value: String
+    }
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldError::WrongType { identifier, expected } => {
+                write!(f, "field {} should be {}", identifier, expected)
+            },
+            FieldError::InvalidValue { identifier, value } => {
+                write!(f, "field {} can't be {}", identifier, value)
+            }
+        }
+    }
+}
+
+// Typed lookups of LDtk fields by their identifier. A field that doesn't
+// exist or isn't set is Ok(None), only values that make no sense are errors
+pub trait LdtkFields {
+    fn field(&self, identifier: &str) -> Option<&FieldValue>;
+
+    fn enum_field<T: std::str::FromStr>(&self, identifier: &str) -> Result<Option<T>, FieldError> {
+        match self.field(identifier) {
+            Some(FieldValue::Enum(Some(value))) => value.parse().map(Some).map_err(|_| FieldError::InvalidValue {
+                identifier: identifier.to_string(),
+                value: value.clone()
+            }),
+            Some(FieldValue::Enum(None)) | None => Ok(None),
+            Some(_) => Err(FieldError::WrongType { identifier: identifier.to_string(), expected: "an enum" })
+        }
+    }
+
+    fn int_field(&self, identifier: &str) -> Result<Option<i32>, FieldError> {
+        match self.field(identifier) {
+            Some(FieldValue::Int(value)) => Ok(*value),
+            None => Ok(None),
+            Some(_) => Err(FieldError::WrongType { identifier: identifier.to_string(), expected: "an int" })
+        }
+    }
+
+    // Ints that count something, so they can't be negative
+    fn count_field(&self, identifier: &str) -> Result<Option<usize>, FieldError> {
+        match self.int_field(identifier)? {
+            Some(value) if value < 0 => Err(FieldError::InvalidValue {
+                identifier: identifier.to_string(),
+                value: value.to_string()
+            }),
+            value => Ok(value.map(|value| value as usize))
+        }
+    }
+
+    // LDtk counts rows from the top, this doesn't flip them
+    fn point_field(&self, identifier: &str) -> Result<Option<IVec2>, FieldError> {
+        match self.field(identifier) {
+            Some(FieldValue::Point(value)) => Ok(*value),
+            None => Ok(None),
+            Some(_) => Err(FieldError::WrongType { identifier: identifier.to_string(), expected: "a point" })
+        }
+    }
+
+    fn string_field(&self, identifier: &str) -> Result<Option<String>, FieldError> {
+        match self.field(identifier) {
+            Some(FieldValue::String(value)) => Ok(value.clone()),
+            None => Ok(None),
+            Some(_) => Err(FieldError::WrongType { identifier: identifier.to_string(), expected: "a string" })
+        }
+    }
+
+    fn bool_field(&self, identifier: &str) -> Result<Option<bool>, FieldError> {
+        match self.field(identifier) {
+            Some(FieldValue::Bool(value)) => Ok(Some(*value)),
+            None => Ok(None),
+            Some(_) => Err(FieldError::WrongType { identifier: identifier.to_string(), expected: "a bool" })
+        }
+    }
+
+    // iids of the referenced entities, works for single references and arrays of them
+    fn entity_refs_field(&self, identifier: &str) -> Result<Vec<String>, FieldError> {
+        match self.field(identifier) {
+            Some(FieldValue::EntityRefs(references)) => Ok(references
+                .iter()
+                .flatten()
+                .map(|reference| reference.entity_iid.clone())
+                .collect()
+            ),
+            Some(FieldValue::EntityRef(reference)) => Ok(reference
+                .iter()
+                .map(|reference| reference.entity_iid.clone())
+                .collect()
+            ),
+            None => Ok(Vec::new()),
+            Some(_) => Err(FieldError::WrongType { identifier: identifier.to_string(), expected: "an entity reference" })
+        }
+    }
+}
+
+impl LdtkFields for EntityInstance {
+    fn field(&self, identifier: &str) -> Option<&FieldValue> {
+        self.field_instances
+            .iter()
+            .find(|field_instance| field_instance.identifier == identifier)
+            .map(|field_instance| &field_instance.value)
+    }
+}
+
+impl LdtkFields for ldtk::Level {
+    fn field(&self, identifier: &str) -> Option<&FieldValue> {
+        self.field_instances
+            .iter()
+            .find(|field_instance| field_instance.identifier == identifier)
+            .map(|field_instance| &field_instance.value)
+    }
+}
+
+// The value of the field, or default if it isn't set or can't be read. Errors get kept in errors
+pub fn field_or<T>(result: Result<Option<T>, FieldError>, default: T, errors: &mut Vec<FieldError>) -> T {
+    match result {
+        Ok(value) => value.unwrap_or(default),
+        Err(error) => {
+            errors.push(error);
+            default
+        }
+    }
+}
+
+// Like field_or, for fields that are fine to leave unset
+pub fn optional_field<T>(result: Result<Option<T>, FieldError>, errors: &mut Vec<FieldError>) -> Option<T> {
+    field_or(result.map(|value| value.map(Some)), None, errors)
+}
+
+// Everything wrong with the fields of an entity. The bundles fall back to
+// defaults for these so the level still loads, this is for reporting them
+pub fn entity_field_errors(entity_instance: &EntityInstance) -> Vec<FieldError> {
+    let results = match entity_instance.identifier.as_str() {
+        "Time_machine_part" => vec![
+            TimeMachinePartType::try_get_type(entity_instance).map(|_| ()),
+            PushableTimeMachine::try_get_pushable(entity_instance).map(|_| ()),
+            TimeMachineKind::try_get_kind(entity_instance).map(|_| ()),
+            entity_instance.bool_field("Opening").map(|_| ())
+        ],
+        "Door" => vec![
+            DoorPlates::try_get_plates(entity_instance).map(|_| ())
+        ],
+        "ActiveIndicator" => vec![
+            ActiveIndicator::try_get_indicator(entity_instance).map(|_| ())
+        ],
+        _ => Vec::new()
+    };
+
+    results.into_iter().filter_map(Result::err).collect()
+}
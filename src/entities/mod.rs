@@ -1,5 +1,29 @@
 use super::*;
 
+mod time_machine_edges;
+pub use time_machine_edges::*;
+
+mod fields;
+pub use fields::*;
+
+mod custom;
+pub use custom::*;
+
+mod timed_gate;
+pub use timed_gate::*;
+
+// Every sprite comes out of tileset_alt.png, 8 by 8 tiles of TILE_SIZE
+pub fn tileset_atlas(texture_atlases: &mut Assets<TextureAtlas>, asset_server: &AssetServer) -> Handle<TextureAtlas> {
+    texture_atlases.add(TextureAtlas::from_grid(
+        asset_server.load("tileset_alt.png"),
+        Vec2::new(TILE_SIZE as f32, TILE_SIZE as f32),
+        8,
+        8,
+        None,
+        None
+    ))
+}
+
 #[derive(Default, Component)]
 pub struct Player;
 
@@ -12,6 +36,18 @@ pub struct Box;
 #[derive(Default, Component)]
 pub struct TimeMachine;
 
+#[derive(Default, Component)]
+pub struct Plate;
+
+#[derive(Default, Component)]
+pub struct Door;
+
+#[derive(Default, Component)]
+pub struct Key;
+
+#[derive(Default, Component)]
+pub struct Lock;
+
 #[derive(Default, Component, Debug, Clone)]
 pub struct GridEntityInfo {
     pub variant: &'static str,
@@ -22,13 +58,7 @@ pub struct GridEntityInfo {
 
 impl GridEntityInfo {
     pub fn from(grid_entity: &GridEntity) -> Self {
-        match grid_entity {
-            GridEntity::Player { .. } => Self { variant: "Player", ..default() },
-            GridEntity::PastPlayer { id, .. } => Self { variant: "PastPlayer", id: *id, ..default() },
-            GridEntity::Box { id } => Self { variant: "Box", id: *id, ..default() },
-            GridEntity::TimeMachine { id, .. } => Self { variant: "TimeMachine", id: *id, ..default() },
-            GridEntity::None => Self::default()
-        }
+        Self { variant: grid_entity.variant(), id: grid_entity.id(), ..default() }
     }
 
     pub fn player(_: EntityInstance) -> Self {
@@ -66,6 +96,66 @@ impl GridEntityInfo {
             time_machine_depth: 0
         }
     }
+
+    fn plate(_: EntityInstance) -> Self {
+        Self {
+            variant: "Plate",
+            id: 0,
+            pos: (0, 0),
+            time_machine_depth: 0
+        }
+    }
+
+    fn door(_: EntityInstance) -> Self {
+        Self {
+            variant: "Door",
+            id: 0,
+            pos: (0, 0),
+            time_machine_depth: 0
+        }
+    }
+
+    fn key(_: EntityInstance) -> Self {
+        Self {
+            variant: "Key",
+            id: 0,
+            pos: (0, 0),
+            time_machine_depth: 0
+        }
+    }
+
+    fn lock(_: EntityInstance) -> Self {
+        Self {
+            variant: "Lock",
+            id: 0,
+            pos: (0, 0),
+            time_machine_depth: 0
+        }
+    }
+}
+
+// The LDtk iid, entity reference fields point at these
+#[derive(Default, Component, Debug, Clone, PartialEq)]
+pub struct LdtkIid(pub String);
+
+impl LdtkIid {
+    pub fn get_iid(entity_instance: EntityInstance) -> Self {
+        Self(entity_instance.iid)
+    }
+}
+
+// iids of the plates a door is linked to, the door is open while all of them are pressed
+#[derive(Default, Component, Debug, Clone, PartialEq)]
+pub struct DoorPlates(pub Vec<String>);
+
+impl DoorPlates {
+    pub fn get_plates(entity_instance: EntityInstance) -> Self {
+        Self::try_get_plates(&entity_instance).unwrap_or_default()
+    }
+
+    pub fn try_get_plates(entity_instance: &EntityInstance) -> Result<Self, FieldError> {
+        entity_instance.entity_refs_field("Plates").map(Self)
+    }
 }
 
 #[derive(Bundle, LdtkEntity)]
@@ -104,7 +194,59 @@ pub struct BoxBundle {
     pub sprite_bundle: SpriteSheetBundle
 }
 
-#[derive(Default, Component, Debug, Clone, Copy, PartialEq)]
+#[derive(Bundle, LdtkEntity)]
+pub struct PlateBundle {
+    pub component: Plate,
+    #[with(GridEntityInfo::plate)]
+    pub grid_entity: GridEntityInfo,
+    #[with(LdtkIid::get_iid)]
+    pub iid: LdtkIid,
+    #[grid_coords]
+    pub position: GridCoords,
+    #[sprite_sheet_bundle]
+    #[bundle]
+    pub sprite_bundle: SpriteSheetBundle
+}
+
+#[derive(Bundle, LdtkEntity)]
+pub struct DoorBundle {
+    pub component: Door,
+    #[with(GridEntityInfo::door)]
+    pub grid_entity: GridEntityInfo,
+    #[with(DoorPlates::get_plates)]
+    pub plates: DoorPlates,
+    #[grid_coords]
+    pub position: GridCoords,
+    #[sprite_sheet_bundle]
+    #[bundle]
+    pub sprite_bundle: SpriteSheetBundle
+}
+
+#[derive(Bundle, LdtkEntity)]
+pub struct KeyBundle {
+    pub component: Key,
+    #[with(GridEntityInfo::key)]
+    pub grid_entity: GridEntityInfo,
+    #[grid_coords]
+    pub position: GridCoords,
+    #[sprite_sheet_bundle]
+    #[bundle]
+    pub sprite_bundle: SpriteSheetBundle
+}
+
+#[derive(Bundle, LdtkEntity)]
+pub struct LockBundle {
+    pub component: Lock,
+    #[with(GridEntityInfo::lock)]
+    pub grid_entity: GridEntityInfo,
+    #[grid_coords]
+    pub position: GridCoords,
+    #[sprite_sheet_bundle]
+    #[bundle]
+    pub sprite_bundle: SpriteSheetBundle
+}
+
+#[derive(Default, Component, Debug, Clone, Copy, PartialEq, Hash)]
 pub enum TimeMachinePartType {
     TopLeftFull,
     TopRightFull,
@@ -203,548 +345,69 @@ impl TimeMachinePartType {
         }
     }
 
+    // Two parts are in the same time machine when the edge between them is Inside on both sides.
+    // This used to be a table per side, which didn't always agree with the table for the other
+    // side and turned down some pairs that fit, like Middle under TopFull
     pub fn fits_on_top(&self, next: &Self) -> bool {
-        match (self, next) {
-            // Straight up cannot have anything connecting to the top
-            (part_type, _) if [
-                TimeMachinePartType::TopLeftFull,
-                TimeMachinePartType::TopRightFull,
-                TimeMachinePartType::TopFull,
-
-                TimeMachinePartType::TopTangentLeft,
-                TimeMachinePartType::TopTangentRight,
-
-                TimeMachinePartType::LeftPerpTop,
-                TimeMachinePartType::RightPerpTop,
-                TimeMachinePartType::TopPerpLeft,
-                TimeMachinePartType::TopPerpRight,
-
-                TimeMachinePartType::TopOpening,
-                TimeMachinePartType::BottomOpening,
-                TimeMachinePartType::LeftOpening,
-                TimeMachinePartType::RightOpening,
-
-                TimeMachinePartType::TopLeftTangentRight,
-                TimeMachinePartType::TopRightTangentLeft,
-                TimeMachinePartType::TopLeftTangentBottom,
-                TimeMachinePartType::TopRightTangentBottom,
-                TimeMachinePartType::MiddleTopOpen,
-            ].contains(part_type) => false,
-
-            // Left line connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::BottomLeftFull,
-                TimeMachinePartType::LeftFull,
-
-                TimeMachinePartType::LeftTangentBottom,
-                TimeMachinePartType::LeftPerpBottom,
-                
-                TimeMachinePartType::BottomLeftTangentRight,
-            ].contains(part_type1) && [
-                TimeMachinePartType::TopLeftFull,
-                TimeMachinePartType::LeftFull,
-
-                TimeMachinePartType::LeftTangentTop,
-                TimeMachinePartType::LeftPerpTop,
-                
-                TimeMachinePartType::TopLeftTangentRight,
-            ].contains(part_type2) => true,
-
-            // Left edge connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::MiddleLeftOpen,
-                TimeMachinePartType::BottomLeftTangentTop,
-                TimeMachinePartType::TopPerpLeft
-            ].contains(part_type1) && [
-                TimeMachinePartType::MiddleLeftOpen,
-                TimeMachinePartType::TopLeftTangentBottom,
-                TimeMachinePartType::BottomPerpLeft
-            ].contains(part_type2) => true,
-
-            // Right line connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::BottomRightFull,
-                TimeMachinePartType::RightFull,
-
-                TimeMachinePartType::RightTangentBottom,
-                TimeMachinePartType::RightPerpBottom,
-                
-                TimeMachinePartType::BottomRightTangentLeft,
-            ].contains(part_type1) && [
-                TimeMachinePartType::TopRightFull,
-                TimeMachinePartType::RightFull,
-
-                TimeMachinePartType::RightTangentTop,
-                TimeMachinePartType::RightPerpTop,
-                
-                TimeMachinePartType::TopRightTangentLeft,
-            ].contains(part_type2) => true,
-            
-            // Right edge connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::MiddleRightOpen,
-                TimeMachinePartType::BottomRightTangentTop,
-                TimeMachinePartType::BottomPerpRight
-            ].contains(part_type1) && [
-                TimeMachinePartType::MiddleRightOpen,
-                TimeMachinePartType::TopRightTangentBottom,
-                TimeMachinePartType::TopPerpRight
-            ].contains(part_type2) => true,
-
-            // Whole connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::Middle,
-                TimeMachinePartType::BottomFull,
-
-                TimeMachinePartType::BottomTangentLeft,
-                TimeMachinePartType::BottomTangentRight,
-
-                TimeMachinePartType::MiddleBottomOpen,
-            ].contains(part_type1) && [
-                TimeMachinePartType::Middle,
-                TimeMachinePartType::TopFull,
-
-                TimeMachinePartType::TopTangentLeft,
-                TimeMachinePartType::TopTangentRight,
-
-                TimeMachinePartType::MiddleTopOpen,
-            ].contains(part_type2) => true,
-            (_, _) => false
-        }
+        self.edges().top == Edge::Inside && next.edges().bottom == Edge::Inside
     }
-    
+
     pub fn fits_on_bottom(&self, next: &Self) -> bool {
-        match (self, next) {
-            // Straight up cannot have anything connecting to the top
-            (part_type, _) if [
-                TimeMachinePartType::BottomLeftFull,
-                TimeMachinePartType::BottomRightFull,
-                TimeMachinePartType::TopFull,
-
-                TimeMachinePartType::BottomTangentLeft,
-                TimeMachinePartType::BottomTangentRight,
-
-                TimeMachinePartType::LeftPerpBottom,
-                TimeMachinePartType::RightPerpBottom,
-                TimeMachinePartType::BottomPerpLeft,
-                TimeMachinePartType::BottomPerpRight,
-
-                TimeMachinePartType::TopOpening,
-                TimeMachinePartType::BottomOpening,
-                TimeMachinePartType::LeftOpening,
-                TimeMachinePartType::RightOpening,
-
-                TimeMachinePartType::BottomLeftTangentRight,
-                TimeMachinePartType::BottomRightTangentLeft,
-                TimeMachinePartType::BottomLeftTangentTop,
-                TimeMachinePartType::BottomRightTangentTop,
-                TimeMachinePartType::MiddleBottomOpen,
-            ].contains(part_type) => false,
-
-            // Left line connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::TopLeftFull,
-                TimeMachinePartType::LeftFull,
-
-                TimeMachinePartType::LeftTangentTop,
-                TimeMachinePartType::LeftPerpTop,
-                
-                TimeMachinePartType::TopLeftTangentRight,
-            ].contains(part_type1) && [
-                TimeMachinePartType::BottomLeftFull,
-                TimeMachinePartType::LeftFull,
-
-                TimeMachinePartType::LeftTangentBottom,
-                TimeMachinePartType::LeftPerpBottom,
-                
-                TimeMachinePartType::BottomLeftTangentRight,
-            ].contains(part_type2) => true,
-
-            // Left edge connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::MiddleLeftOpen,
-                TimeMachinePartType::TopLeftTangentBottom,
-                TimeMachinePartType::BottomPerpLeft
-            ].contains(part_type1) && [
-                TimeMachinePartType::MiddleLeftOpen,
-                TimeMachinePartType::BottomLeftTangentTop,
-                TimeMachinePartType::TopPerpLeft
-            ].contains(part_type2) => true,
-
-            // Right line connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::TopRightFull,
-                TimeMachinePartType::RightFull,
-
-                TimeMachinePartType::RightTangentTop,
-                TimeMachinePartType::RightPerpTop,
-                
-                TimeMachinePartType::TopRightTangentLeft,
-            ].contains(part_type1) && [
-                TimeMachinePartType::BottomRightFull,
-                TimeMachinePartType::RightFull,
-
-                TimeMachinePartType::RightTangentBottom,
-                TimeMachinePartType::RightPerpBottom,
-                
-                TimeMachinePartType::BottomRightTangentLeft,
-            ].contains(part_type2) => true,
-            
-            // Right edge connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::MiddleRightOpen,
-                TimeMachinePartType::TopRightTangentBottom,
-                TimeMachinePartType::BottomPerpRight
-            ].contains(part_type1) && [
-                TimeMachinePartType::MiddleRightOpen,
-                TimeMachinePartType::BottomRightTangentTop,
-                TimeMachinePartType::TopPerpRight
-            ].contains(part_type2) => true,
-
-            // Whole connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::Middle,
-                TimeMachinePartType::TopFull,
-
-                TimeMachinePartType::TopTangentLeft,
-                TimeMachinePartType::TopTangentRight,
-
-                TimeMachinePartType::MiddleTopOpen,
-            ].contains(part_type1) && [
-                TimeMachinePartType::Middle,
-                TimeMachinePartType::BottomFull,
-
-                TimeMachinePartType::BottomTangentLeft,
-                TimeMachinePartType::BottomTangentRight,
-
-                TimeMachinePartType::MiddleBottomOpen,
-            ].contains(part_type2) => true,
-            (_, _) => false
-        }
+        self.edges().bottom == Edge::Inside && next.edges().top == Edge::Inside
     }
 
     pub fn fits_on_left(&self, next: &Self) -> bool {
-        match (self, next) {
-            // Straight up cannot have anything connecting to the top
-            (part_type, _) if [
-                TimeMachinePartType::BottomLeftFull,
-                TimeMachinePartType::TopLeftFull,
-                TimeMachinePartType::LeftFull,
-
-                TimeMachinePartType::LeftTangentTop,
-                TimeMachinePartType::LeftTangentBottom,
-
-                TimeMachinePartType::TopPerpLeft,
-                TimeMachinePartType::BottomPerpLeft,
-                TimeMachinePartType::LeftPerpTop,
-                TimeMachinePartType::LeftPerpBottom,
-
-                TimeMachinePartType::TopOpening,
-                TimeMachinePartType::BottomOpening,
-                TimeMachinePartType::LeftOpening,
-                TimeMachinePartType::RightOpening,
-
-                TimeMachinePartType::BottomLeftTangentRight,
-                TimeMachinePartType::TopLeftTangentRight,
-                TimeMachinePartType::BottomLeftTangentTop,
-                TimeMachinePartType::TopLeftTangentBottom,
-                TimeMachinePartType::MiddleLeftOpen,
-            ].contains(part_type) => false,
-
-            // Top line connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::TopRightFull,
-                TimeMachinePartType::TopFull,
-
-                TimeMachinePartType::TopTangentRight,
-                TimeMachinePartType::TopPerpRight,
-                
-                TimeMachinePartType::TopRightTangentBottom,
-            ].contains(part_type1) && [
-                TimeMachinePartType::TopLeftFull,
-                TimeMachinePartType::TopFull,
-
-                TimeMachinePartType::TopTangentLeft,
-                TimeMachinePartType::TopPerpLeft,
-                
-                TimeMachinePartType::TopLeftTangentBottom,
-            ].contains(part_type2) => true,
-
-            // Top edge connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::MiddleTopOpen,
-                TimeMachinePartType::TopRightTangentLeft,
-                TimeMachinePartType::RightPerpTop
-            ].contains(part_type1) && [
-                TimeMachinePartType::MiddleTopOpen,
-                TimeMachinePartType::TopLeftTangentRight,
-                TimeMachinePartType::LeftPerpTop
-            ].contains(part_type2) => true,
-
-            // Bottom line connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::BottomRightFull,
-                TimeMachinePartType::BottomFull,
-
-                TimeMachinePartType::BottomTangentRight,
-                TimeMachinePartType::BottomPerpRight,
-                
-                TimeMachinePartType::BottomRightTangentTop,
-            ].contains(part_type1) && [
-                TimeMachinePartType::BottomLeftFull,
-                TimeMachinePartType::BottomFull,
-
-                TimeMachinePartType::BottomTangentLeft,
-                TimeMachinePartType::BottomPerpLeft,
-                
-                TimeMachinePartType::BottomLeftTangentTop,
-            ].contains(part_type2) => true,
-            
-            // Bottom edge connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::MiddleBottomOpen,
-                TimeMachinePartType::BottomRightTangentLeft,
-                TimeMachinePartType::RightPerpBottom
-            ].contains(part_type1) && [
-                TimeMachinePartType::MiddleBottomOpen,
-                TimeMachinePartType::BottomLeftTangentRight,
-                TimeMachinePartType::LeftPerpBottom
-            ].contains(part_type2) => true,
-
-            // Whole connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::Middle,
-                TimeMachinePartType::RightFull,
-
-                TimeMachinePartType::RightTangentTop,
-                TimeMachinePartType::RightTangentBottom,
-
-                TimeMachinePartType::MiddleRightOpen,
-            ].contains(part_type1) && [
-                TimeMachinePartType::Middle,
-                TimeMachinePartType::LeftFull,
-
-                TimeMachinePartType::LeftTangentTop,
-                TimeMachinePartType::LeftTangentBottom,
-
-                TimeMachinePartType::MiddleLeftOpen,
-            ].contains(part_type2) => true,
-            (_, _) => false
-        }
+        self.edges().left == Edge::Inside && next.edges().right == Edge::Inside
     }
 
     pub fn fits_on_right(&self, next: &Self) -> bool {
-        match (self, next) {
-            // Straight up cannot have anything connecting to the top
-            (part_type, _) if [
-                TimeMachinePartType::BottomRightFull,
-                TimeMachinePartType::TopRightFull,
-                TimeMachinePartType::RightFull,
-
-                TimeMachinePartType::RightTangentTop,
-                TimeMachinePartType::RightTangentBottom,
-
-                TimeMachinePartType::TopPerpRight,
-                TimeMachinePartType::BottomPerpRight,
-                TimeMachinePartType::RightPerpTop,
-                TimeMachinePartType::RightPerpBottom,
-
-                TimeMachinePartType::TopOpening,
-                TimeMachinePartType::BottomOpening,
-                TimeMachinePartType::RightOpening,
-                TimeMachinePartType::LeftOpening,
-
-                TimeMachinePartType::BottomRightTangentLeft,
-                TimeMachinePartType::TopRightTangentLeft,
-                TimeMachinePartType::BottomRightTangentTop,
-                TimeMachinePartType::TopRightTangentBottom,
-                TimeMachinePartType::MiddleRightOpen,
-            ].contains(part_type) => false,
-
-            // Top line connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::TopLeftFull,
-                TimeMachinePartType::TopFull,
-
-                TimeMachinePartType::TopTangentLeft,
-                TimeMachinePartType::TopPerpLeft,
-                
-                TimeMachinePartType::TopLeftTangentBottom,
-            ].contains(part_type1) && [
-                TimeMachinePartType::TopRightFull,
-                TimeMachinePartType::TopFull,
-
-                TimeMachinePartType::TopTangentRight,
-                TimeMachinePartType::TopPerpRight,
-                
-                TimeMachinePartType::TopRightTangentBottom,
-            ].contains(part_type2) => true,
-
-            // Top edge connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::MiddleTopOpen,
-                TimeMachinePartType::TopLeftTangentRight,
-                TimeMachinePartType::LeftPerpTop
-            ].contains(part_type1) && [
-                TimeMachinePartType::MiddleTopOpen,
-                TimeMachinePartType::TopRightTangentLeft,
-                TimeMachinePartType::RightPerpTop
-            ].contains(part_type2) => true,
-
-            // Bottom line connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::BottomLeftFull,
-                TimeMachinePartType::BottomFull,
-
-                TimeMachinePartType::BottomTangentLeft,
-                TimeMachinePartType::BottomPerpLeft,
-                
-                TimeMachinePartType::BottomLeftTangentTop,
-            ].contains(part_type1) && [
-                TimeMachinePartType::BottomRightFull,
-                TimeMachinePartType::BottomFull,
-
-                TimeMachinePartType::BottomTangentRight,
-                TimeMachinePartType::BottomPerpRight,
-                
-                TimeMachinePartType::BottomRightTangentTop,
-            ].contains(part_type2) => true,
-            
-            // Bottom edge connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::MiddleBottomOpen,
-                TimeMachinePartType::BottomLeftTangentRight,
-                TimeMachinePartType::LeftPerpBottom
-            ].contains(part_type1) && [
-                TimeMachinePartType::MiddleBottomOpen,
-                TimeMachinePartType::BottomRightTangentLeft,
-                TimeMachinePartType::RightPerpBottom
-            ].contains(part_type2) => true,
-
-            // Whole connection
-            (part_type1, part_type2) if [
-                TimeMachinePartType::Middle,
-                TimeMachinePartType::LeftFull,
-
-                TimeMachinePartType::LeftTangentTop,
-                TimeMachinePartType::LeftTangentBottom,
-
-                TimeMachinePartType::MiddleLeftOpen,
-            ].contains(part_type1) && [
-                TimeMachinePartType::Middle,
-                TimeMachinePartType::RightFull,
-
-                TimeMachinePartType::RightTangentTop,
-                TimeMachinePartType::RightTangentBottom,
-
-                TimeMachinePartType::MiddleRightOpen,
-            ].contains(part_type2) => true,
-            (_, _) => false
-        }
+        self.edges().right == Edge::Inside && next.edges().left == Edge::Inside
     }
 
-
     pub fn can_enter_exit_top(&self) -> bool {
-        match self {
-            TimeMachinePartType::TopLeftFull |
-            TimeMachinePartType::TopRightFull |
-            TimeMachinePartType::TopFull |
-
-            TimeMachinePartType::TopTangentLeft |
-            TimeMachinePartType::TopTangentRight |
-            TimeMachinePartType::TopPerpLeft |
-            TimeMachinePartType::TopPerpRight |
-
-            TimeMachinePartType::LeftOpening |
-            TimeMachinePartType::BottomOpening |
-            TimeMachinePartType::RightOpening |
-
-            TimeMachinePartType::TopLeftTangentRight |
-            TimeMachinePartType::TopLeftTangentBottom |
-            TimeMachinePartType::TopRightTangentLeft |
-            TimeMachinePartType::TopRightTangentBottom => false,
-            _ => true
-        }
+        self.edges().top != Edge::Wall
     }
 
     pub fn can_enter_exit_bottom(&self) -> bool {
-        match self {
-            TimeMachinePartType::BottomLeftFull |
-            TimeMachinePartType::BottomRightFull |
-            TimeMachinePartType::BottomFull |
-
-            TimeMachinePartType::BottomTangentLeft |
-            TimeMachinePartType::BottomTangentRight |
-            TimeMachinePartType::BottomPerpLeft |
-            TimeMachinePartType::BottomPerpRight |
-
-            TimeMachinePartType::LeftOpening |
-            TimeMachinePartType::TopOpening |
-            TimeMachinePartType::RightOpening |
-
-            TimeMachinePartType::BottomLeftTangentRight |
-            TimeMachinePartType::BottomLeftTangentTop |
-            TimeMachinePartType::BottomRightTangentLeft |
-            TimeMachinePartType::BottomRightTangentTop => false,
-            _ => true
-        }
+        self.edges().bottom != Edge::Wall
     }
 
     pub fn can_enter_exit_left(&self) -> bool {
-        match self {
-            TimeMachinePartType::TopLeftFull |
-            TimeMachinePartType::BottomLeftFull |
-            TimeMachinePartType::LeftFull |
-
-            TimeMachinePartType::LeftTangentTop |
-            TimeMachinePartType::LeftTangentBottom |
-            TimeMachinePartType::LeftPerpTop |
-            TimeMachinePartType::LeftPerpBottom |
-
-            TimeMachinePartType::TopOpening |
-            TimeMachinePartType::BottomOpening |
-            TimeMachinePartType::RightOpening |
-
-            TimeMachinePartType::TopLeftTangentRight |
-            TimeMachinePartType::TopLeftTangentBottom |
-            TimeMachinePartType::BottomLeftTangentRight |
-            TimeMachinePartType::BottomLeftTangentTop => false,
-            _ => true
-        }
+        self.edges().left != Edge::Wall
     }
 
     pub fn can_enter_exit_right(&self) -> bool {
-        match self {
-            TimeMachinePartType::TopRightFull |
-            TimeMachinePartType::BottomRightFull |
-            TimeMachinePartType::RightFull |
-
-            TimeMachinePartType::RightTangentTop |
-            TimeMachinePartType::RightTangentBottom |
-            TimeMachinePartType::RightPerpTop |
-            TimeMachinePartType::RightPerpBottom |
-
-            TimeMachinePartType::TopOpening |
-            TimeMachinePartType::BottomOpening |
-            TimeMachinePartType::LeftOpening |
-
-            TimeMachinePartType::TopRightTangentLeft |
-            TimeMachinePartType::TopRightTangentBottom |
-            TimeMachinePartType::BottomRightTangentLeft |
-            TimeMachinePartType::BottomRightTangentTop => false,
-            _ => true
+        self.edges().right != Edge::Wall
+    }
+
+    // Whether something inside can leave through this part's side facing direction
+    pub fn can_exit_towards(&self, direction: MoveDirection) -> bool {
+        match direction {
+            MoveDirection::Up => self.can_enter_exit_top(),
+            MoveDirection::Down => self.can_enter_exit_bottom(),
+            MoveDirection::Left => self.can_enter_exit_left(),
+            MoveDirection::Right => self.can_enter_exit_right()
+        }
+    }
+
+    // Whether something moving in direction can come in through the opposite side of this part
+    pub fn can_enter_moving(&self, direction: MoveDirection) -> bool {
+        match direction {
+            MoveDirection::Up => self.can_enter_exit_bottom(),
+            MoveDirection::Down => self.can_enter_exit_top(),
+            MoveDirection::Left => self.can_enter_exit_right(),
+            MoveDirection::Right => self.can_enter_exit_left()
         }
     }
 
+    // Parts without a type get Middle for now, autotile_time_machine_parts works out
+    // their real type from the parts around them
     pub fn get_type(entity_instance: EntityInstance) -> Self {
-        entity_instance
-            .field_instances[0]
-            .real_editor_values[0]
-            .as_ref().unwrap()
-            .get("params").unwrap()
-            .get(0).unwrap()
-            .as_str().unwrap()
-            .parse().unwrap()
+        Self::try_get_type(&entity_instance).unwrap_or_default()
+    }
+
+    pub fn try_get_type(entity_instance: &EntityInstance) -> Result<Self, FieldError> {
+        entity_instance.enum_field("TimeMachineType").map(Option::unwrap_or_default)
     }
 }
 
@@ -799,7 +462,83 @@ impl std::str::FromStr for TimeMachinePartType {
             "MiddleBottomOpen" => Ok(TimeMachinePartType::MiddleBottomOpen),
             "MiddleLeftOpen" => Ok(TimeMachinePartType::MiddleLeftOpen),
             "MiddleRightOpen" => Ok(TimeMachinePartType::MiddleRightOpen),
-            s => {println!("{s}"); Err(())}
+            _ => Err(())
+        }
+    }
+}
+
+// If any part of a time machine is pushable the whole time machine is
+#[derive(Default, Component, Debug, Clone, Copy, PartialEq)]
+pub struct PushableTimeMachine(pub bool);
+
+impl PushableTimeMachine {
+    pub fn get_pushable(entity_instance: EntityInstance) -> Self {
+        Self::try_get_pushable(&entity_instance).unwrap_or_default()
+    }
+
+    pub fn try_get_pushable(entity_instance: &EntityInstance) -> Result<Self, FieldError> {
+        entity_instance.bool_field("Pushable").map(|pushable| Self(pushable.unwrap_or(false)))
+    }
+}
+
+// Rewind machines send their contents back in time, stasis machines take them
+// out of the grid and put them back ticks later, or at the next activation if ticks is 0.
+// If any part of a time machine is a stasis part the whole time machine is
+#[derive(Default, Component, Debug, Clone, Copy, PartialEq, Hash)]
+pub enum TimeMachineKind {
+    #[default]
+    Rewind,
+    Stasis {
+        ticks: usize
+    }
+}
+
+impl TimeMachineKind {
+    pub fn get_kind(entity_instance: EntityInstance) -> Self {
+        Self::try_get_kind(&entity_instance).unwrap_or_default()
+    }
+
+    pub fn try_get_kind(entity_instance: &EntityInstance) -> Result<Self, FieldError> {
+        match entity_instance.enum_field::<TimeMachineKind>("Kind")? {
+            Some(TimeMachineKind::Stasis { .. }) => match entity_instance.int_field("StasisTicks")? {
+                Some(ticks) if ticks < 0 => Err(FieldError::InvalidValue {
+                    identifier: "StasisTicks".to_string(),
+                    value: ticks.to_string()
+                }),
+                ticks => Ok(Self::Stasis { ticks: ticks.unwrap_or(0) as usize })
+            },
+            _ => Ok(Self::Rewind)
+        }
+    }
+}
+
+// Only the names from LDtk, the ticks come from a separate field
+impl std::str::FromStr for TimeMachineKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Rewind" => Ok(TimeMachineKind::Rewind),
+            "Stasis" => Ok(TimeMachineKind::Stasis { ticks: 0 }),
+            _ => Err(())
+        }
+    }
+}
+
+// Parts painted without a TimeMachineType get autotiled, opening marks that
+// the part's outside sides can be walked through
+#[derive(Default, Component, Debug, Clone, Copy, PartialEq)]
+pub struct TimeMachineCell {
+    pub autotile: bool,
+    pub opening: bool
+}
+
+impl TimeMachineCell {
+    pub fn get_cell(entity_instance: EntityInstance) -> Self {
+        Self {
+            // Parts with a type that couldn't be read get autotiled too
+            autotile: !matches!(entity_instance.enum_field::<TimeMachinePartType>("TimeMachineType"), Ok(Some(_))),
+            opening: entity_instance.bool_field("Opening").ok().flatten().unwrap_or(false)
         }
     }
 }
@@ -809,6 +548,14 @@ pub struct TimeMachinePartBundle {
     pub component: TimeMachine,
     #[with(TimeMachinePartType::get_type)]
     pub part_type: TimeMachinePartType,
+    #[with(PushableTimeMachine::get_pushable)]
+    pub pushable: PushableTimeMachine,
+    #[with(TimeMachineKind::get_kind)]
+    pub kind: TimeMachineKind,
+    #[with(TimeMachineCell::get_cell)]
+    pub cell: TimeMachineCell,
+    #[with(LdtkIid::get_iid)]
+    pub iid: LdtkIid,
     #[with(GridEntityInfo::time_machine)]
     pub grid_entity: GridEntityInfo,
     #[grid_coords]
@@ -816,4 +563,39 @@ pub struct TimeMachinePartBundle {
     #[sprite_sheet_bundle]
     #[bundle]
     pub sprite_bundle: SpriteSheetBundle
+}
+
+// Shows the state of a time machine, the one its Machine field points at or
+// otherwise the one it's placed on. Machines without one get a small one added
+#[derive(Default, Component, Debug, Clone, PartialEq)]
+pub struct ActiveIndicator {
+    // iid of a part of the machine from the Machine field
+    pub machine_iid: Option<String>,
+    pub machine_id: Option<usize>,
+    // From the bottom left of the machine's corner cell, so the indicator follows the machine around
+    pub offset: Vec2
+}
+
+impl ActiveIndicator {
+    pub fn get_indicator(entity_instance: EntityInstance) -> Self {
+        Self::try_get_indicator(&entity_instance).unwrap_or_default()
+    }
+
+    pub fn try_get_indicator(entity_instance: &EntityInstance) -> Result<Self, FieldError> {
+        Ok(Self {
+            machine_iid: entity_instance.entity_refs_field("Machine")?.into_iter().next(),
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Bundle, LdtkEntity)]
+pub struct ActiveIndicatorBundle {
+    #[with(ActiveIndicator::get_indicator)]
+    pub indicator: ActiveIndicator,
+    #[grid_coords]
+    pub position: GridCoords,
+    #[sprite_sheet_bundle]
+    #[bundle]
+    pub sprite_bundle: SpriteSheetBundle
 }
\ No newline at end of file
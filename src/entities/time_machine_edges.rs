@@ -0,0 +1,352 @@
+use super::*;
+
+// What one side of a time machine part is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    // Connects to another part of the same time machine
+    Inside,
+    // Outside of the time machine, can't be walked through
+    Wall,
+    // Outside of the time machine, can be walked through
+    Open
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeMachineEdges {
+    pub top: Edge,
+    pub bottom: Edge,
+    pub left: Edge,
+    pub right: Edge
+}
+
+impl TimeMachineEdges {
+    pub fn new(top: Edge, bottom: Edge, left: Edge, right: Edge) -> Self {
+        Self { top, bottom, left, right }
+    }
+
+    pub fn get(&self, side: MoveDirection) -> Edge {
+        match side {
+            MoveDirection::Up => self.top,
+            MoveDirection::Down => self.bottom,
+            MoveDirection::Left => self.left,
+            MoveDirection::Right => self.right
+        }
+    }
+
+    pub fn set(&mut self, side: MoveDirection, edge: Edge) {
+        match side {
+            MoveDirection::Up => self.top = edge,
+            MoveDirection::Down => self.bottom = edge,
+            MoveDirection::Left => self.left = edge,
+            MoveDirection::Right => self.right = edge
+        }
+    }
+
+    fn mismatches(&self, other: &Self) -> usize {
+        [
+            self.top == other.top,
+            self.bottom == other.bottom,
+            self.left == other.left,
+            self.right == other.right
+        ].into_iter().filter(|matches| !matches).count()
+    }
+}
+
+impl TimeMachinePartType {
+    // Plain variants come first so from_edges picks them over the tangent ones,
+    // which only differ in how they look
+    pub const ALL: [TimeMachinePartType; 41] = [
+        TimeMachinePartType::Middle,
+
+        TimeMachinePartType::TopLeftFull,
+        TimeMachinePartType::TopRightFull,
+        TimeMachinePartType::BottomLeftFull,
+        TimeMachinePartType::BottomRightFull,
+
+        TimeMachinePartType::TopFull,
+        TimeMachinePartType::BottomFull,
+        TimeMachinePartType::LeftFull,
+        TimeMachinePartType::RightFull,
+
+        TimeMachinePartType::LeftPerpTop,
+        TimeMachinePartType::RightPerpTop,
+        TimeMachinePartType::LeftPerpBottom,
+        TimeMachinePartType::RightPerpBottom,
+        TimeMachinePartType::TopPerpLeft,
+        TimeMachinePartType::BottomPerpLeft,
+        TimeMachinePartType::TopPerpRight,
+        TimeMachinePartType::BottomPerpRight,
+
+        TimeMachinePartType::TopOpening,
+        TimeMachinePartType::BottomOpening,
+        TimeMachinePartType::LeftOpening,
+        TimeMachinePartType::RightOpening,
+
+        TimeMachinePartType::MiddleTopOpen,
+        TimeMachinePartType::MiddleBottomOpen,
+        TimeMachinePartType::MiddleLeftOpen,
+        TimeMachinePartType::MiddleRightOpen,
+
+        TimeMachinePartType::LeftTangentTop,
+        TimeMachinePartType::RightTangentTop,
+        TimeMachinePartType::LeftTangentBottom,
+        TimeMachinePartType::RightTangentBottom,
+        TimeMachinePartType::TopTangentLeft,
+        TimeMachinePartType::BottomTangentLeft,
+        TimeMachinePartType::TopTangentRight,
+        TimeMachinePartType::BottomTangentRight,
+
+        TimeMachinePartType::TopLeftTangentRight,
+        TimeMachinePartType::BottomLeftTangentRight,
+        TimeMachinePartType::TopRightTangentLeft,
+        TimeMachinePartType::BottomRightTangentLeft,
+        TimeMachinePartType::TopLeftTangentBottom,
+        TimeMachinePartType::BottomLeftTangentTop,
+        TimeMachinePartType::TopRightTangentBottom,
+        TimeMachinePartType::BottomRightTangentTop
+    ];
+
+    // Tangent parts have the same edges as the full ones, the wall just ends
+    // with a little curve where it meets an opening
+    pub fn edges(&self) -> TimeMachineEdges {
+        use Edge::*;
+
+        let (top, bottom, left, right) = match self {
+            TimeMachinePartType::Middle => (Inside, Inside, Inside, Inside),
+
+            TimeMachinePartType::TopLeftFull |
+            TimeMachinePartType::TopLeftTangentRight |
+            TimeMachinePartType::TopLeftTangentBottom => (Wall, Inside, Wall, Inside),
+            TimeMachinePartType::TopRightFull |
+            TimeMachinePartType::TopRightTangentLeft |
+            TimeMachinePartType::TopRightTangentBottom => (Wall, Inside, Inside, Wall),
+            TimeMachinePartType::BottomLeftFull |
+            TimeMachinePartType::BottomLeftTangentRight |
+            TimeMachinePartType::BottomLeftTangentTop => (Inside, Wall, Wall, Inside),
+            TimeMachinePartType::BottomRightFull |
+            TimeMachinePartType::BottomRightTangentLeft |
+            TimeMachinePartType::BottomRightTangentTop => (Inside, Wall, Inside, Wall),
+
+            TimeMachinePartType::TopFull |
+            TimeMachinePartType::TopTangentLeft |
+            TimeMachinePartType::TopTangentRight => (Wall, Inside, Inside, Inside),
+            TimeMachinePartType::BottomFull |
+            TimeMachinePartType::BottomTangentLeft |
+            TimeMachinePartType::BottomTangentRight => (Inside, Wall, Inside, Inside),
+            TimeMachinePartType::LeftFull |
+            TimeMachinePartType::LeftTangentTop |
+            TimeMachinePartType::LeftTangentBottom => (Inside, Inside, Wall, Inside),
+            TimeMachinePartType::RightFull |
+            TimeMachinePartType::RightTangentTop |
+            TimeMachinePartType::RightTangentBottom => (Inside, Inside, Inside, Wall),
+
+            // XPerpY has a wall on X and the Y side open
+            TimeMachinePartType::LeftPerpTop => (Open, Inside, Wall, Inside),
+            TimeMachinePartType::RightPerpTop => (Open, Inside, Inside, Wall),
+            TimeMachinePartType::LeftPerpBottom => (Inside, Open, Wall, Inside),
+            TimeMachinePartType::RightPerpBottom => (Inside, Open, Inside, Wall),
+            TimeMachinePartType::TopPerpLeft => (Wall, Inside, Open, Inside),
+            TimeMachinePartType::BottomPerpLeft => (Inside, Wall, Open, Inside),
+            TimeMachinePartType::TopPerpRight => (Wall, Inside, Inside, Open),
+            TimeMachinePartType::BottomPerpRight => (Inside, Wall, Inside, Open),
+
+            // Single part time machines
+            TimeMachinePartType::TopOpening => (Open, Wall, Wall, Wall),
+            TimeMachinePartType::BottomOpening => (Wall, Open, Wall, Wall),
+            TimeMachinePartType::LeftOpening => (Wall, Wall, Open, Wall),
+            TimeMachinePartType::RightOpening => (Wall, Wall, Wall, Open),
+
+            TimeMachinePartType::MiddleTopOpen => (Open, Inside, Inside, Inside),
+            TimeMachinePartType::MiddleBottomOpen => (Inside, Open, Inside, Inside),
+            TimeMachinePartType::MiddleLeftOpen => (Inside, Inside, Open, Inside),
+            TimeMachinePartType::MiddleRightOpen => (Inside, Inside, Inside, Open)
+        };
+
+        TimeMachineEdges::new(top, bottom, left, right)
+    }
+
+    // Some edge combinations (like two open sides meeting in a corner) don't have a part,
+    // those get the closest one there is
+    pub fn from_edges(edges: TimeMachineEdges) -> Self {
+        let part_type = *Self::ALL
+            .iter()
+            .min_by_key(|part_type| part_type.edges().mismatches(&edges))
+            .unwrap();
+
+        if part_type.edges() != edges {
+            warn!("No time machine part has edges {:?}, using {:?}", edges, part_type);
+        }
+
+        part_type
+    }
+
+    // The version of this part where the wall on wall_side stops towards the towards side
+    pub fn with_tangent(&self, wall_side: MoveDirection, towards: MoveDirection) -> Option<Self> {
+        use MoveDirection::*;
+
+        match (self, wall_side, towards) {
+            (TimeMachinePartType::TopFull, Up, Left) => Some(TimeMachinePartType::TopTangentLeft),
+            (TimeMachinePartType::TopFull, Up, Right) => Some(TimeMachinePartType::TopTangentRight),
+            (TimeMachinePartType::BottomFull, Down, Left) => Some(TimeMachinePartType::BottomTangentLeft),
+            (TimeMachinePartType::BottomFull, Down, Right) => Some(TimeMachinePartType::BottomTangentRight),
+            (TimeMachinePartType::LeftFull, Left, Up) => Some(TimeMachinePartType::LeftTangentTop),
+            (TimeMachinePartType::LeftFull, Left, Down) => Some(TimeMachinePartType::LeftTangentBottom),
+            (TimeMachinePartType::RightFull, Right, Up) => Some(TimeMachinePartType::RightTangentTop),
+            (TimeMachinePartType::RightFull, Right, Down) => Some(TimeMachinePartType::RightTangentBottom),
+
+            (TimeMachinePartType::TopLeftFull, Up, Right) => Some(TimeMachinePartType::TopLeftTangentRight),
+            (TimeMachinePartType::TopLeftFull, Left, Down) => Some(TimeMachinePartType::TopLeftTangentBottom),
+            (TimeMachinePartType::TopRightFull, Up, Left) => Some(TimeMachinePartType::TopRightTangentLeft),
+            (TimeMachinePartType::TopRightFull, Right, Down) => Some(TimeMachinePartType::TopRightTangentBottom),
+            (TimeMachinePartType::BottomLeftFull, Down, Right) => Some(TimeMachinePartType::BottomLeftTangentRight),
+            (TimeMachinePartType::BottomLeftFull, Left, Up) => Some(TimeMachinePartType::BottomLeftTangentTop),
+            (TimeMachinePartType::BottomRightFull, Down, Left) => Some(TimeMachinePartType::BottomRightTangentLeft),
+            (TimeMachinePartType::BottomRightFull, Right, Up) => Some(TimeMachinePartType::BottomRightTangentTop),
+            _ => None
+        }
+    }
+
+    // Index into tileset_alt.png, same as the tileIds of the TimeMachineType enum in LDtk
+    pub fn tile_index(&self) -> usize {
+        match self {
+            TimeMachinePartType::TopLeftFull => 12,
+            TimeMachinePartType::LeftFull => 20,
+            TimeMachinePartType::BottomLeftFull => 28,
+            TimeMachinePartType::TopFull => 13,
+            TimeMachinePartType::Middle => 21,
+            TimeMachinePartType::BottomFull => 29,
+            TimeMachinePartType::TopRightFull => 14,
+            TimeMachinePartType::RightFull => 22,
+            TimeMachinePartType::BottomRightFull => 30,
+            TimeMachinePartType::LeftTangentTop => 16,
+            TimeMachinePartType::RightTangentTop => 17,
+            TimeMachinePartType::LeftTangentBottom => 24,
+            TimeMachinePartType::RightTangentBottom => 25,
+            TimeMachinePartType::TopTangentLeft => 2,
+            TimeMachinePartType::BottomTangentLeft => 10,
+            TimeMachinePartType::TopTangentRight => 3,
+            TimeMachinePartType::BottomTangentRight => 11,
+            TimeMachinePartType::LeftPerpTop => 32,
+            TimeMachinePartType::RightPerpTop => 33,
+            TimeMachinePartType::LeftPerpBottom => 40,
+            TimeMachinePartType::RightPerpBottom => 41,
+            TimeMachinePartType::TopPerpLeft => 34,
+            TimeMachinePartType::BottomPerpLeft => 42,
+            TimeMachinePartType::TopPerpRight => 35,
+            TimeMachinePartType::BottomPerpRight => 43,
+            TimeMachinePartType::TopOpening => 45,
+            TimeMachinePartType::BottomOpening => 37,
+            TimeMachinePartType::LeftOpening => 44,
+            TimeMachinePartType::RightOpening => 36,
+            TimeMachinePartType::TopLeftTangentRight => 0,
+            TimeMachinePartType::BottomLeftTangentRight => 8,
+            TimeMachinePartType::TopRightTangentLeft => 1,
+            TimeMachinePartType::BottomRightTangentLeft => 9,
+            TimeMachinePartType::TopLeftTangentBottom => 18,
+            TimeMachinePartType::BottomLeftTangentTop => 26,
+            TimeMachinePartType::TopRightTangentBottom => 19,
+            TimeMachinePartType::BottomRightTangentTop => 27,
+            TimeMachinePartType::MiddleLeftOpen => 5,
+            TimeMachinePartType::MiddleRightOpen => 7,
+            TimeMachinePartType::MiddleTopOpen => 4,
+            TimeMachinePartType::MiddleBottomOpen => 6
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TimeMachinePartType::*;
+
+    #[test]
+    fn from_edges_gives_back_every_plain_part() {
+        // Tangent parts share their edges with a full part, which comes first in ALL
+        for part_type in TimeMachinePartType::ALL {
+            let expected = [
+                TopLeftFull, TopRightFull, BottomLeftFull, BottomRightFull,
+                TopFull, BottomFull, LeftFull, RightFull
+            ]
+            .into_iter()
+            .find(|full| full.edges() == part_type.edges())
+            .unwrap_or(part_type);
+
+            assert_eq!(TimeMachinePartType::from_edges(part_type.edges()), expected, "{:?}", part_type);
+        }
+    }
+
+    #[test]
+    fn from_edges_falls_back_to_the_closest_part() {
+        use Edge::*;
+
+        let cases = [
+            // Two open sides meeting in a corner
+            (TimeMachineEdges::new(Open, Inside, Open, Inside), LeftPerpTop),
+            // A part by itself with nothing open
+            (TimeMachineEdges::new(Wall, Wall, Wall, Wall), TopOpening),
+            // Open on two opposite sides
+            (TimeMachineEdges::new(Inside, Inside, Open, Open), MiddleLeftOpen)
+        ];
+
+        for (edges, expected) in cases {
+            assert_eq!(TimeMachinePartType::from_edges(edges), expected, "{:?}", edges);
+        }
+    }
+
+    #[test]
+    fn fits_on_agrees_from_both_sides() {
+        for a in TimeMachinePartType::ALL {
+            for b in TimeMachinePartType::ALL {
+                assert_eq!(a.fits_on_top(&b), b.fits_on_bottom(&a), "{:?} under {:?}", a, b);
+                assert_eq!(a.fits_on_right(&b), b.fits_on_left(&a), "{:?} left of {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn fits_on_edge_combinations() {
+        // (part, part above it, fits)
+        let vertical = [
+            (Middle, Middle, true),
+            (Middle, TopFull, true),
+            (BottomFull, Middle, true),
+            (BottomFull, TopFull, true),
+            (LeftFull, TopLeftFull, true),
+            (LeftTangentBottom, TopLeftTangentRight, true),
+            (MiddleLeftOpen, Middle, true),
+            (Middle, BottomOpening, false),
+            (Middle, LeftPerpTop, true),
+            (MiddleBottomOpen, Middle, true),
+            (MiddleTopOpen, Middle, false),
+            (TopFull, BottomFull, false),
+            (TopOpening, BottomOpening, false),
+            (BottomOpening, TopOpening, false),
+            (BottomLeftFull, TopLeftFull, true),
+            (TopLeftFull, BottomLeftFull, false)
+        ];
+
+        for (part_type, above, fits) in vertical {
+            assert_eq!(part_type.fits_on_top(&above), fits, "{:?} under {:?}", part_type, above);
+        }
+
+        // (part, part right of it, fits)
+        let horizontal = [
+            (Middle, Middle, true),
+            (LeftFull, Middle, true),
+            (Middle, RightFull, true),
+            (TopLeftFull, TopFull, true),
+            (TopLeftTangentRight, TopTangentLeft, true),
+            (MiddleTopOpen, Middle, true),
+            (Middle, TopPerpLeft, false),
+            (MiddleRightOpen, Middle, false),
+            (RightFull, LeftFull, false),
+            (LeftOpening, RightOpening, false),
+            (RightOpening, LeftOpening, false),
+            (TopRightFull, TopLeftFull, false)
+        ];
+
+        for (part_type, right, fits) in horizontal {
+            assert_eq!(part_type.fits_on_right(&right), fits, "{:?} left of {:?}", part_type, right);
+        }
+    }
+}
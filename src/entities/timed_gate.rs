@@ -0,0 +1,134 @@
+use super::*;
+use serde_json::json;
+
+// How many ticks a gate stays closed and then open when the Period field isn't set
+pub const DEFAULT_GATE_PERIOD: usize = 2;
+
+// A gate in the floor that opens and closes on its own every period ticks, starting closed.
+// It can't shut on whatever is standing in it, it waits until the cell is free
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimedGate {
+    pub id: usize,
+    pub period: usize,
+    pub open: bool
+}
+
+impl EntityBehaviour for TimedGate {
+    fn from_instance(entity_instance: &EntityInstance) -> Self {
+        let period = entity_instance
+            .count_field("Period")
+            .unwrap_or_else(|error| {
+                warn!("Timed gate: {}", error);
+                None
+            })
+            .filter(|period| *period > 0)
+            .unwrap_or(DEFAULT_GATE_PERIOD);
+
+        Self { id: 0, period, open: false }
+    }
+
+    fn to_field_instances(&self) -> Vec<(&'static str, serde_json::Value)> {
+        vec![("Period", json!(self.period))]
+    }
+
+    fn variant(&self) -> &'static str { "Timed_gate" }
+    fn id(&self) -> usize { self.id }
+    fn set_id(&mut self, id: usize) { self.id = id; }
+
+    fn is_floor(&self) -> bool { true }
+    fn is_blocking(&self) -> bool { !self.open }
+    fn is_pushable(&self) -> bool { false }
+
+    fn update(&mut self, pos: (usize, usize), tick: usize, grid: &Grid) {
+        self.open = (tick / self.period) % 2 == 1 || grid.grid_index(pos.0, pos.1) != 0;
+    }
+
+    // The door tile, tinted so it doesn't get mistaken for one
+    fn tile_index(&self) -> usize { 51 }
+
+    fn color(&self) -> Color {
+        if self.open { Color::rgba(1.0, 1.0, 0.0, 0.25) } else { Color::YELLOW }
+    }
+
+    fn serialize(&self) -> String {
+        format!("{} {} {}", self.id, self.period, self.open)
+    }
+
+    fn clone_box(&self) -> std::boxed::Box<dyn EntityBehaviour> {
+        std::boxed::Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A player with a box to its right and a gate right of the box
+    fn gate_level(period: usize) -> Grid {
+        let mut grid = Grid::new_sized(4, 1);
+        grid.add_entity_to_pos(0, 0, &mut GridEntityInfo { variant: "Player", ..default() });
+        grid.add_entity_to_pos(1, 0, &mut GridEntityInfo { variant: "Box", id: 1, ..default() });
+        grid.add_custom_entity_to_pos(
+            2,
+            0,
+            CustomEntity(std::boxed::Box::new(TimedGate { id: 2, period, open: false })),
+            &mut GridEntityInfo { variant: "Timed_gate", id: 2, ..default() }
+        );
+
+        grid
+    }
+
+    fn gate_open(grid: &Grid) -> bool {
+        match grid.get_entity_from_id("Timed_gate", 2) {
+            Some((_, GridEntity::Custom(custom))) => !custom.0.is_blocking(),
+            _ => panic!("The gate is gone")
+        }
+    }
+
+    fn push_box(grid: &mut Grid) -> Result<(), MoveBlocked> {
+        let player = grid.get_entity_index_from_id("Player", 0).unwrap();
+
+        grid.try_move(player, MoveDirection::Right)
+    }
+
+    #[test]
+    fn gates_are_floor_that_can_not_be_pushed() {
+        let gate = TimedGate { id: 2, period: 2, open: false };
+
+        assert!(gate.is_floor() && !gate.is_pushable() && !gate.presses_plates());
+    }
+
+    #[test]
+    fn closed_gates_block_pushed_boxes() {
+        let mut grid = gate_level(2);
+        let before = grid.clone();
+
+        assert_eq!(push_box(&mut grid), Err(MoveBlocked::Blocked("Timed_gate", 2)));
+        assert_eq!(grid, before);
+    }
+
+    #[test]
+    fn gates_open_and_close_every_period() {
+        let mut grid = gate_level(2);
+
+        let open_at: Vec<bool> = (0..6).map(|tick| {
+            grid.update_events(tick);
+            gate_open(&grid)
+        }).collect();
+
+        assert_eq!(open_at, vec![false, false, true, true, false, false]);
+    }
+
+    #[test]
+    fn open_gates_let_boxes_through_and_stay_open_under_them() {
+        let mut grid = gate_level(1);
+        grid.update_events(1);
+
+        assert_eq!(push_box(&mut grid), Ok(()));
+        assert_eq!(grid.get_entity_from_id("Box", 1).map(|(pos, _)| *pos), Some((2, 0)));
+
+        // Closing time, but the box is in the way
+        grid.update_events(2);
+        assert!(gate_open(&grid));
+    }
+}
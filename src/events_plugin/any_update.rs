@@ -1,27 +1,59 @@
 use super::*;
 
-pub fn any_update_happened(
-    key_event: Res<KeyEvent>
-) -> bool {
-    key_event.to_ivec().is_some()
-}
-
-pub fn update_movements(
+pub fn update_actions(
     mut grid: ResMut<Grid>,
-    key_event: Res<KeyEvent>
+    mut tick_events: EventReader<TickAdvanced>
 ) {
-    grid.add_movement(key_event.to_ivec().unwrap());
+    for tick_event in tick_events.iter() {
+        grid.add_action(tick_event.action);
+    }
 }
 
-pub fn update_ticks(mut ticks: ResMut<Ticks>) {
-    ticks.0 += 1;
+// Only actions that take a tick advance it, activations get handled by enable_activate_time_machine
+pub fn update_ticks(
+    mut ticks: ResMut<Ticks>,
+    mut action_requests: EventReader<PlayerActionRequested>,
+    mut tick_events: EventWriter<TickAdvanced>
+) {
+    for PlayerActionRequested(action) in action_requests.iter() {
+        if action.takes_tick() {
+            ticks.0 += 1;
+            tick_events.send(TickAdvanced { tick: ticks.0, action: *action });
+        }
+    }
 }
 
+// Past players do their next action and machines whose time has come go off
 pub fn update_events(
     mut grid: ResMut<Grid>,
-    ticks: ResMut<Ticks>,
-    mut clicked: ResMut<ClickedTimeMachine>
+    level_info: Res<LevelInfo>,
+    mut tick_events: EventReader<TickAdvanced>,
+    mut activated_events: EventWriter<TimeMachineActivated>,
+    mut moved_events: EventWriter<EntityMoved>,
+    mut pushed_events: EventWriter<EntityPushed>
 ) {
-    grid.update_events(ticks.0, &mut clicked);
-    // println!("update events: {}, clicked: {:?}", *grid, clicked.0);
-}
\ No newline at end of file
+    for tick_event in tick_events.iter() {
+        let activated = {
+            let _span = debug_span!("movement", tick = tick_event.tick).entered();
+            let before = grid.clone();
+
+            let activated = grid.update_events(tick_event.tick);
+            trace!("update events: {}, activated: {:?}", *grid, activated);
+
+            send_move_events(&before, &grid, &mut moved_events, &mut pushed_events);
+            activated
+        };
+
+        if let Some((time_machine_info, activator)) = activated {
+            let _span = info_span!("time_travel", machine = time_machine_info.id, tick = tick_event.tick).entered();
+            let result = activate_time_machine(&mut grid, &time_machine_info, activator, tick_event.tick, &level_info);
+            report_activation(&time_machine_info, &result);
+
+            activated_events.send(TimeMachineActivated { time_machine: time_machine_info, by_player: false, result });
+        }
+    }
+}
+
+pub fn update_plates(mut grid: ResMut<Grid>) {
+    grid.update_plates();
+}
@@ -0,0 +1,73 @@
+use super::*;
+
+// What the game sends out as things happen, so other plugins like audio or the UI
+// can react without knowing about the systems in EventsPlugin
+
+// Something the player wants to do, from a key press, a click or a replay
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlayerActionRequested(pub Action);
+
+// The player's action took a tick, tick is the new tick
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TickAdvanced {
+    pub tick: usize,
+    pub action: Action
+}
+
+// Sent for refused activations too, result says what happened
+#[derive(Clone, Debug)]
+pub struct TimeMachineActivated {
+    pub time_machine: GridEntityInfo,
+    // False for past players and machines going off on their own
+    pub by_player: bool,
+    pub result: Result<Activation, ActivationRefused>
+}
+
+// A player or past player walked from one cell to another
+#[derive(Clone, Debug)]
+pub struct EntityMoved {
+    pub entity: GridEntityInfo,
+    pub from: (usize, usize),
+    pub to: (usize, usize)
+}
+
+// Something that got pushed along by a move, from and to are its corner
+#[derive(Clone, Debug)]
+pub struct EntityPushed {
+    pub entity: GridEntityInfo,
+    pub from: (usize, usize),
+    pub to: (usize, usize)
+}
+
+// The player got to the goal of the level, sent every time they step onto it
+#[derive(Clone, Debug)]
+pub struct LevelCompleted {
+    pub identifier: String
+}
+
+// Compares the grid from before a move with the one after it. Players and past players
+// moved themselves, everything else was pushed
+pub fn send_move_events(
+    before: &Grid,
+    after: &Grid,
+    moved_events: &mut EventWriter<EntityMoved>,
+    pushed_events: &mut EventWriter<EntityPushed>
+) {
+    for (index, from, to) in after.moved_since(before) {
+        let grid_entity = &after.entities_iter().nth(index).unwrap().1;
+        let entity = GridEntityInfo::from(grid_entity);
+
+        match grid_entity {
+            GridEntity::Player { .. } | GridEntity::PastPlayer { .. } => moved_events.send(EntityMoved { entity, from, to }),
+            _ => pushed_events.send(EntityPushed { entity, from, to })
+        }
+    }
+}
+
+pub fn anything_happened(
+    mut tick_events: EventReader<TickAdvanced>,
+    mut activated_events: EventReader<TimeMachineActivated>
+) -> bool {
+    // Both get read through so the events don't count again next frame
+    tick_events.iter().count() + activated_events.iter().count() > 0
+}
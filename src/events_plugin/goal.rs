@@ -0,0 +1,19 @@
+use super::*;
+
+// The level is finished once the player stands on the goal. Only arriving counts,
+// standing still on it doesn't send the event again
+pub fn check_goal(
+    grid: Res<Grid>,
+    level_info: Res<LevelInfo>,
+    mut on_goal: Local<bool>,
+    mut completed_events: EventWriter<LevelCompleted>
+) {
+    let player_pos = grid.get_entity_from_id("Player", 0).map(|(pos, _)| *pos);
+    let was_on_goal = *on_goal;
+
+    *on_goal = level_info.goal.is_some() && player_pos == level_info.goal;
+
+    if *on_goal && !was_on_goal {
+        completed_events.send(LevelCompleted { identifier: level_info.identifier.clone() });
+    }
+}
@@ -0,0 +1,167 @@
+use super::*;
+use bevy::input::{keyboard::KeyboardInput, ButtonState};
+use std::collections::VecDeque;
+
+// Presses past this get dropped, so mashing keys can't line up more moves than
+// the player can still see coming
+pub const MAX_QUEUED_ACTIONS: usize = 3;
+
+// Actions waiting to be run, one gets requested every tick
+#[derive(Resource, Default)]
+pub struct InputQueue(pub VecDeque<Action>);
+
+impl InputQueue {
+    pub fn push(&mut self, action: Action) {
+        if self.0.len() < MAX_QUEUED_ACTIONS {
+            self.0.push_back(action);
+        }
+    }
+}
+
+// Times are in seconds
+#[derive(Resource)]
+pub struct KeyRepeat {
+    pub delay: f32,
+    pub interval: f32
+}
+
+impl Default for KeyRepeat {
+    fn default() -> Self {
+        Self {
+            delay: 0.3,
+            interval: 0.12
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct HeldKey {
+    // Movement keys that are down, in the order they were pressed
+    pressed: Vec<KeyCode>,
+    // The one that repeats, always the last one pressed that's still down
+    key: Option<KeyCode>,
+    held_for: f32,
+    next_repeat: f32
+}
+
+pub fn action_from_key_code(key_code: KeyCode) -> Option<Action> {
+    match key_code {
+        KeyCode::W => Some(Action::Move(MoveDirection::Up)),
+        KeyCode::A => Some(Action::Move(MoveDirection::Left)),
+        KeyCode::S => Some(Action::Move(MoveDirection::Down)),
+        KeyCode::D => Some(Action::Move(MoveDirection::Right)),
+        KeyCode::Space => Some(Action::Wait),
+        _ => None
+    }
+}
+
+// Uses the keyboard events instead of Input<KeyCode> so that keys pressed
+// in the same frame keep the order they were typed in
+pub fn queue_key_presses(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    input: Res<Input<KeyCode>>,
+    mut input_queue: ResMut<InputQueue>
+) {
+    let mut queued: Vec<KeyCode> = Vec::new();
+
+    for keyboard_event in keyboard_events.iter() {
+        if let (Some(key_code), ButtonState::Pressed) = (keyboard_event.key_code, keyboard_event.state) {
+            // Os key repeats also send pressed events, holding is handled by queue_held_key
+            if !input.just_pressed(key_code) || queued.contains(&key_code) { continue; }
+
+            if let Some(action) = action_from_key_code(key_code) {
+                input_queue.push(action);
+                queued.push(key_code);
+            }
+        }
+    }
+}
+
+pub fn queue_held_key(
+    time: Res<Time>,
+    input: Res<Input<KeyCode>>,
+    key_repeat: Res<KeyRepeat>,
+    mut input_queue: ResMut<InputQueue>,
+    mut held_key: Local<HeldKey>
+) {
+    // Only movement keys repeat, holding space shouldn't skip a bunch of ticks
+    let just_pressed: Vec<KeyCode> = [KeyCode::W, KeyCode::A, KeyCode::S, KeyCode::D]
+        .into_iter()
+        .filter(|key_code| input.just_pressed(*key_code))
+        .collect();
+
+    held_key.pressed.retain(|key_code| input.pressed(*key_code) && !just_pressed.contains(key_code));
+    held_key.pressed.extend(just_pressed.iter().copied());
+
+    let key_code = held_key.pressed.last().copied();
+
+    // Letting go of the newest key goes back to repeating the one held before it,
+    // which starts over with the delay like a new press
+    if !just_pressed.is_empty() || key_code != held_key.key {
+        held_key.key = key_code;
+        held_key.held_for = 0.0;
+        held_key.next_repeat = key_repeat.delay;
+
+        return;
+    }
+
+    let key_code = if let Some(key_code) = key_code { key_code } else { return; };
+
+    held_key.held_for += time.delta_seconds();
+
+    // Doesn't add repeats while the queue still has stuff in it so holding a key can't build up a backlog
+    if held_key.held_for >= held_key.next_repeat && input_queue.0.is_empty() {
+        input_queue.push(action_from_key_code(key_code).unwrap());
+        held_key.next_repeat = held_key.held_for + key_repeat.interval;
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queue_drops_presses_past_the_cap() {
+        let mut input_queue = InputQueue::default();
+
+        for _ in 0..MAX_QUEUED_ACTIONS + 2 {
+            input_queue.push(Action::Move(MoveDirection::Up));
+        }
+
+        assert_eq!(input_queue.0.len(), MAX_QUEUED_ACTIONS);
+    }
+
+    #[test]
+    fn newest_held_key_repeats() {
+        let mut app = App::new();
+
+        app
+            .insert_resource(Input::<KeyCode>::default())
+            .insert_resource(Time::default())
+            .insert_resource(KeyRepeat { delay: 0.0, interval: 0.0 })
+            .insert_resource(InputQueue::default())
+            .add_system(queue_held_key);
+
+        let press = |app: &mut App, key_code: KeyCode| {
+            let mut input = app.world.resource_mut::<Input<KeyCode>>();
+            input.clear();
+            input.press(key_code);
+        };
+
+        press(&mut app, KeyCode::W);
+        app.update();
+        press(&mut app, KeyCode::D);
+        app.update();
+
+        // W is still down, but D got pressed after it
+        app.world.resource_mut::<Input<KeyCode>>().clear();
+        app.update();
+        assert_eq!(app.world.resource::<InputQueue>().0.front(), Some(&Action::Move(MoveDirection::Right)));
+
+        app.world.resource_mut::<InputQueue>().0.clear();
+        app.world.resource_mut::<Input<KeyCode>>().release(KeyCode::D);
+        app.update();
+        app.world.resource_mut::<Input<KeyCode>>().clear();
+        app.update();
+        assert_eq!(app.world.resource::<InputQueue>().0.front(), Some(&Action::Move(MoveDirection::Up)));
+    }
+}
@@ -0,0 +1,48 @@
+use super::*;
+
+pub fn count_tick_action(
+    mut level_stats: ResMut<LevelStats>,
+    mut tick_events: EventReader<TickAdvanced>
+) {
+    for tick_event in tick_events.iter() {
+        level_stats.count_action(tick_event.action);
+    }
+}
+
+pub fn count_level_time(
+    mut level_stats: ResMut<LevelStats>,
+    time: Res<Time>
+) {
+    level_stats.count_time(time.delta());
+}
+
+// The first time the goal is reached decides the result, the best result of each level gets saved
+pub fn record_level_result(
+    level_info: Res<LevelInfo>,
+    mut level_stats: ResMut<LevelStats>,
+    mut best_results: ResMut<BestResults>,
+    mut completed_events: EventReader<LevelCompleted>
+) {
+    for completed in completed_events.iter() {
+        if level_stats.result.is_some() { continue; }
+
+        let result = level_stats.complete(&level_info);
+
+        info!(
+            "Finished {} with {}/{} stars: {} moves, {} waits, {} time travels in {:.1}s",
+            level_info.name,
+            result.stars,
+            LevelResult::MAX_STARS,
+            result.moves,
+            result.waits,
+            result.time_travels,
+            result.seconds
+        );
+
+        if best_results.submit(&completed.identifier, result) {
+            if let Err(error) = best_results.save() {
+                error!("Couldn't save the best results: {}", error);
+            }
+        }
+    }
+}
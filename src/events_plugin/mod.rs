@@ -10,184 +10,168 @@ pub use movement::*;
 mod any_update;
 pub use any_update::*;
 
+mod input_queue;
+pub use input_queue::*;
+
+mod goal;
+pub use goal::*;
+
+mod level_stats;
+pub use level_stats::*;
+
+mod game_events;
+pub use game_events::*;
+
 pub struct EventsPlugin;
 
 impl Plugin for EventsPlugin {
     fn build(&self, app: &mut App) {
         app
-            .insert_resource(KeyEvent::None)
-            .insert_resource(ClickedTimeMachine(None))
-            .add_system(set_key_event.label("set key event"))
-            .add_system(set_clicked.label("set clicked"))
+            .add_event::<PlayerActionRequested>()
+            .add_event::<TickAdvanced>()
+            .add_event::<TimeMachineActivated>()
+            .add_event::<EntityMoved>()
+            .add_event::<EntityPushed>()
+            .add_event::<LevelCompleted>()
+            .insert_resource(InputQueue::default())
+            .insert_resource(KeyRepeat::default())
+            .add_system(
+                queue_key_presses
+                .run_if(in_game)
+                .label("queue key presses")
+            )
+            .add_system(
+                queue_held_key
+                .run_if(in_game)
+                .label("queue held key")
+                .after("queue key presses")
+            )
+            .add_system(
+                request_queued_action
+                .label("request queued action")
+                .after("queue held key")
+            )
+            .add_system(set_clicked.run_if(in_game).label("set clicked"))
 
             .add_system(
                 update_ticks
-                .run_if(any_update_happened)
                 .label("update ticks")
-                .after("set key event")
+                .after("request queued action")
                 .after("set clicked")
+            ).add_system(
+                count_tick_action
+                .after("update ticks")
             ).add_system(
                 player_movement
-                .run_if(movement_event_happened)
                 .label("player movement")
                 .after("update ticks")
             ).add_system(
                 update_events
-                .run_if(any_update_happened)
                 .label("update events")
                 .after("player movement")
             ).add_system(
                 enable_activate_time_machine
-                .run_if(time_machine_has_been_clicked)
                 .label("enable/activate tm")
                 .after("update events")
             ).add_system(
-                update_movements
-                .run_if(any_update_happened)
-                .label("update movements")
+                update_plates
+                .run_if(anything_happened)
+                .label("update plates")
                 .after("enable/activate tm")
             ).add_system(
-                print_grid
+                update_actions
+                .label("update actions")
+                .after("update plates")
+            ).add_system(
+                log_grid
                 .run_if(anything_happened)
-                .after("update movements")
+                .after("update actions")
                 .before("update to grid")
             ).add_system(
                 update_to_grid
                 .run_if(anything_happened)
                 .label("update to grid")
-                .after("update movements")
+                .after("update actions")
             ).add_system(
-                reset_clicked
+                check_goal
                 .run_if(anything_happened)
-                .label("reset clicked")
-                .after("update to grid")
+                .label("check goal")
+                .after("update actions")
+            ).add_system(
+                record_level_result
+                .after("check goal")
+            ).add_system(
+                count_level_time
+                .run_if(level_setup_completed)
             )
         ;
     }
 }
 
-fn print_grid(grid: Res<Grid>) { println!("print_grid: {}", *grid); }
+// The whole grid is a lot, so it only shows up with RUST_LOG set to trace
+fn log_grid(grid: Res<Grid>, ticks: Res<Ticks>) { trace!("Grid at tick {}: {}", ticks.0, *grid); }
+
+// The cell of the level the cursor is over, it can still be past the top or right of the level
+pub fn cursor_cell(windows: &Windows, level_window_info: &LevelWindowInfo) -> Option<(usize, usize)> {
+    let cursor_pos = windows.get_primary()?.cursor_position()?;
 
-#[derive(Resource)]
-pub struct ClickedTimeMachine(pub Option<GridEntityInfo>);
+    let cell = (
+        ((cursor_pos.x - level_window_info.offset.0) / (level_window_info.scaling_factor * TILE_SIZE as f32)).floor(),
+        ((cursor_pos.y - level_window_info.offset.1) / (level_window_info.scaling_factor * TILE_SIZE as f32)).floor()
+    );
+
+    if cell.0 < 0.0 || cell.1 < 0.0 { None } else { Some((cell.0 as usize, cell.1 as usize)) }
+}
 
 fn set_clicked(
     grid: Res<Grid>,
     windows: Res<Windows>,
     buttons: Res<Input<MouseButton>>,
-    mut clicked: ResMut<ClickedTimeMachine>,
+    mut action_requests: EventWriter<PlayerActionRequested>,
     time_machine_info_query: Query<&GridEntityInfo, With<TimeMachine>>,
     level_window_info: Res<LevelWindowInfo>
 ) {
     if buttons.just_pressed(MouseButton::Left) {
-        let cursor_pos = windows.get_primary().unwrap().cursor_position().unwrap_or(Vec2::new(-1.0, -1.0));
+        // The cell that was clicked on
+        let cursor_cell = cursor_cell(&windows, &level_window_info);
 
         // Gets the time machine that has been clicked with the most time machine depth
-        clicked.0 = time_machine_info_query.into_iter()
+        let clicked = time_machine_info_query.into_iter()
         .filter(|grid_entity_info| {
-            grid_entity_info.pos == (0, 0) && (if let Some((
-                corner, 
-                GridEntity::TimeMachine { grid: tm_grid, .. }
-            )) = grid.get_entity(grid_entity_info) {
-                let (x1, y1, x2, y2) = (
-                    level_window_info.scaling_factor * (TILE_SIZE * corner.0) as f32 + level_window_info.offset.0,
-                    level_window_info.scaling_factor * (TILE_SIZE * corner.1) as f32 + level_window_info.offset.1,
-                    level_window_info.scaling_factor * (TILE_SIZE * (corner.0 + tm_grid.len())) as f32 + level_window_info.offset.0,
-                    level_window_info.scaling_factor * (TILE_SIZE * (corner.1 + tm_grid[0].len())) as f32 + level_window_info.offset.1
-                );
-
-                println!("{:?}  ---  {:?}", (x1, y1, x2, y2), cursor_pos);
+            let cursor_cell = if let Some(cursor_cell) = cursor_cell { cursor_cell } else { return false; };
 
-                if x1 < cursor_pos.x && cursor_pos.x < x2 && y1 < cursor_pos.y && cursor_pos.y < y2 {
-                    true
-                } else {
-                    false
-                }
+            if let Some((corner, time_machine)) = grid.get_entity(grid_entity_info) {
+                // Uses the mask so clicking in the gaps of non-rectangular time machines doesn't count
+                time_machine.tm_occupies(*corner, cursor_cell)
             } else {
                 false
-            })
+            }
         })
         .max_by_key(|grid_entity_info| {
             grid_entity_info.time_machine_depth
-        }).cloned();
-    }
-}
+        });
 
-#[derive(Resource)]
-pub enum KeyEvent {
-    W,
-    A,
-    S,
-    D,
-    Space,
-    None
-}
+        debug!("Clicked cell {:?}, time machine {:?}", cursor_cell, clicked.map(|time_machine_info| time_machine_info.id));
 
-impl KeyEvent {
-    fn to_direction(&self) -> Option<MoveDirection> {
-        match self {
-            KeyEvent::W => Some(MoveDirection::Up),
-            KeyEvent::A => Some(MoveDirection::Left),
-            KeyEvent::S => Some(MoveDirection::Down),
-            KeyEvent::D => Some(MoveDirection::Right),
-            KeyEvent::Space => None,
-            KeyEvent::None => None,
-        }
-    }
-    
-    pub fn to_ivec(&self) -> Option<IVec2> {
-        match self {
-            Self::W => Some(IVec2::new(0, 1)),
-            Self::A => Some(IVec2::new(-1, 0)),
-            Self::S => Some(IVec2::new(0, -1)),
-            Self::D => Some(IVec2::new(1, 0)),
-            Self::Space => Some(IVec2::ZERO),
-            Self::None => None
+        if let Some(time_machine_info) = clicked {
+            action_requests.send(PlayerActionRequested(Action::Activate(time_machine_info.id)));
         }
     }
 }
 
-pub fn set_key_event(
-    mut input: ResMut<Input<KeyCode>>,
-    mut key_event: ResMut<KeyEvent>
-) {
-    *key_event = if input.just_pressed(KeyCode::W) {
-        input.clear_just_pressed(KeyCode::W);
-        KeyEvent::W
-
-    } else if input.just_pressed(KeyCode::A) {
-        input.clear_just_pressed(KeyCode::A);
-        KeyEvent::A
-
-    } else if input.just_pressed(KeyCode::S) {
-        input.clear_just_pressed(KeyCode::S);
-        KeyEvent::S
-
-    }  else if input.just_pressed(KeyCode::D) {
-        input.clear_just_pressed(KeyCode::D);
-        KeyEvent::D
-
-    } else if input.just_pressed(KeyCode::Space) {
-        input.clear_just_pressed(KeyCode::Space);
-        KeyEvent::Space
-
-    } else {
-        KeyEvent::None
-    }
-}
-
-fn time_machine_has_been_clicked(clicked: Res<ClickedTimeMachine>) -> bool {
-    clicked.0.is_some()
-}
-
-fn anything_happened(clicked: Res<ClickedTimeMachine>, key_event: Res<KeyEvent>) -> bool {
-    time_machine_has_been_clicked(clicked) || any_update_happened(key_event)
+// The game's input is ignored while the editor is open
+pub fn in_game(app_state: Res<State<AppState>>) -> bool {
+    app_state.current() == &AppState::InGame
 }
 
-fn reset_clicked(
-    mut clicked: ResMut<ClickedTimeMachine>
+// Takes one action off the queue per tick, the rest wait for the following ticks
+pub fn request_queued_action(
+    mut input_queue: ResMut<InputQueue>,
+    mut action_requests: EventWriter<PlayerActionRequested>
 ) {
-    clicked.0 = None;
+    if let Some(action) = input_queue.0.pop_front() {
+        action_requests.send(PlayerActionRequested(action));
+    }
 }
 
 pub fn update_to_grid(
@@ -210,11 +194,25 @@ pub fn update_to_grid(
         mut coords, 
         mut grid_entity_info
     ) in entities_query.iter_mut() {
-        if let Some((corner, entity)) = grid.get_entity(&*grid_entity_info) {
-            if let GridEntity::TimeMachine { start_instance: Some(_), .. } = entity {
-                texture.color = Color::RED;
-            } else {
-                texture.color = Color::WHITE;
+        if let Some((corner, entity)) = grid.get_entity(&grid_entity_info) {
+            texture.color = match entity {
+                GridEntity::TimeMachine { kind: TimeMachineKind::Stasis { .. }, .. } => Color::CYAN,
+                GridEntity::Plate { active: true, .. } => Color::GREEN,
+                GridEntity::Door { open: true, .. } => Color::rgba(1.0, 1.0, 1.0, 0.25),
+                GridEntity::Lock { .. } => Color::ORANGE,
+                GridEntity::Custom(custom) => custom.0.color(),
+                _ => Color::WHITE
+            };
+
+            match entity {
+                GridEntity::Custom(custom) => texture.index = custom.0.tile_index(),
+                // The editor can open and close parts
+                GridEntity::TimeMachine { grid: parts, .. } => if let Some((part_type, _)) = parts
+                    .get(grid_entity_info.pos.0)
+                    .and_then(|column| column.get(grid_entity_info.pos.1)) {
+                    texture.index = part_type.tile_index();
+                },
+                _ => {}
             }
 
             grid.set_depth_of(&mut grid_entity_info);
@@ -225,17 +223,7 @@ pub fn update_to_grid(
             update_transform(&coords, &mut transform);
 
             // Removes the particular entity from the vec
-            grid_entities = grid_entities
-                .into_iter()
-                .filter(|(_, grid_entity)| {
-                    !match (grid_entity, grid_entity_info.variant) {
-                        (GridEntity::Player { .. }, "Player") => true,
-                        (GridEntity::PastPlayer { id: entity_id, .. }, "PastPlayer") |
-                        (GridEntity::Box {id: entity_id, .. }, "Box") |
-                        (GridEntity::TimeMachine { id: entity_id, .. }, "TimeMachine") => *entity_id == grid_entity_info.id,
-                        _ => false
-                    }
-                }).collect();
+            grid_entities.retain(|(_, grid_entity)| !grid_entity.is_entity(grid_entity_info.variant, grid_entity_info.id));
         } else {
             // deletes entities that are no longer in grid
             commands.entity(bevy_entity).despawn_recursive();
@@ -1,6 +1,6 @@
 use super::*;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Hash)]
 pub enum MoveDirection {
     Up,
     Down,
@@ -8,7 +8,44 @@ pub enum MoveDirection {
     Right
 }
 
+// Why a move didn't happen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveBlocked {
+    OutOfBounds,
+    // A cell of the Collisions layer
+    Wall,
+    // id of the door
+    ClosedDoor(usize),
+    // id of the lock, only players with a key can get through
+    Locked(usize),
+    // id of the time machine whose wall is in the way
+    TimeMachineWall(usize),
+    // Variant and id of a custom floor entity that's in the way
+    Blocked(&'static str, usize),
+    NotPushable,
+    // Nothing in the grid has the variant and id that was asked to move
+    NotFound
+}
+
 impl MoveDirection {
+    pub const ALL: [MoveDirection; 4] = [MoveDirection::Up, MoveDirection::Down, MoveDirection::Left, MoveDirection::Right];
+
+    pub fn opposite(&self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::Left => Self::Right,
+            Self::Right => Self::Left
+        }
+    }
+
+    pub fn perpendicular(&self) -> [Self; 2] {
+        match self {
+            Self::Up | Self::Down => [Self::Left, Self::Right],
+            Self::Left | Self::Right => [Self::Up, Self::Down]
+        }
+    }
+
     pub fn chnage_coords(&self, coords: &mut GridCoords) {
         match self {
             Self::Up => coords.y += 1,
@@ -27,30 +64,29 @@ impl MoveDirection {
             _                                => None
         }
     } 
-
-    pub fn from_ivec(ivec: IVec2) -> Self {
-        match ivec.to_array() {
-            [0, 1] => MoveDirection::Up,
-            [0, -1] => MoveDirection::Down,
-            [-1, 0] => MoveDirection::Left,
-            [1, 0] => MoveDirection::Right,
-            vec => panic!("Cannot convert {:?} to MoveDirection", vec)
-        }
-    }
-}
-
-pub fn movement_event_happened(key_event: Res<KeyEvent>) -> bool {
-    key_event.to_direction().is_some()
 }
 
 pub fn player_movement(
-    key_event: Res<KeyEvent>,
+    mut tick_events: EventReader<TickAdvanced>,
+    mut moved_events: EventWriter<EntityMoved>,
+    mut pushed_events: EventWriter<EntityPushed>,
     mut query: Query<&GridEntityInfo, With<Player>>,
     mut grid: ResMut<Grid>
 ) {
-    if let Ok(grid_entity_info) = query.get_single_mut() {
-        let direction = key_event.to_direction().unwrap();
-        
-        grid.try_move_entity(grid_entity_info, direction);
+    for tick_event in tick_events.iter() {
+        let (direction, grid_entity_info) = match (tick_event.action, query.get_single_mut()) {
+            (Action::Move(direction), Ok(grid_entity_info)) => (direction, grid_entity_info),
+            _ => continue
+        };
+
+        let _span = debug_span!("movement", tick = tick_event.tick).entered();
+
+        let before = grid.clone();
+
+        if let Err(reason) = grid.try_move_entity(grid_entity_info, direction) {
+            debug!("Player couldn't move {:?}: {:?}", direction, reason);
+        }
+
+        send_move_events(&before, &grid, &mut moved_events, &mut pushed_events);
     }
 }
\ No newline at end of file
@@ -1,34 +1,124 @@
 use super::*;
 
-// !!Assumptions!!
-// - If an entity is on the inside of the time machine, no part of it will be on the outside
+// What an activation ended up doing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Activation {
+    StartedRecording,
+    // The player wasn't inside so the machine stopped and lost its contents
+    Stopped,
+    TimeTravelled,
+    // The contents went back without the player, the present carries on
+    SentBack,
+    Held,
+    Released,
+    // Some of a stasis machine's contents couldn't get out
+    Blocked,
+    Nothing
+}
+
+// Why an activation got refused, the grid is left as it was
+#[derive(Clone, Debug, PartialEq)]
+pub enum ActivationRefused {
+    Duplication(Vec<(usize, usize)>),
+    KeyDuplication(Vec<usize>),
+    PlayerInStasis
+}
+
+impl std::fmt::Display for ActivationRefused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Duplication(duplicated) => write!(f, "Paradox: time travel would duplicate the entities {:?}", duplicated),
+            Self::KeyDuplication(keys) => write!(f, "Paradox: time travel would duplicate the keys {:?}", keys),
+            Self::PlayerInStasis => write!(f, "The player can't be put into stasis")
+        }
+    }
+}
+
+// Activations by the player, the ones by past players happen in update_events
 pub fn enable_activate_time_machine(
-    // mut commands: Commands,
     mut grid: ResMut<Grid>,
     ticks: Res<Ticks>,
-    clicked_time_machine: Res<ClickedTimeMachine>,
+    level_info: Res<LevelInfo>,
+    mut level_stats: ResMut<LevelStats>,
+    mut action_requests: EventReader<PlayerActionRequested>,
+    mut activated_events: EventWriter<TimeMachineActivated>
 ) {
-    // println!("Enable/Activate Time machine (Start): {}", *grid);
-
-    // println!(
-    //     "start_instance: {}\n", 
-    //     if let Some(info) = &clicked_time_machine.0 {
-    //         if let Some((_, GridEntity::TimeMachine { start_instance, .. })) = grid.get_entity(info) {
-    //             format!("{:?}", start_instance)
-    //         } else { String::new() }
-    //     } else { String::new() }
-    // );
-
-    let time_machine_info = clicked_time_machine.0.as_ref().unwrap();
-    let contains_player = grid.tm_contains_index(
-        &grid.get_entity(time_machine_info).unwrap().1, 
-        grid.get_entity_index_from_id("Player", 0).unwrap()
+    for PlayerActionRequested(action) in action_requests.iter() {
+        let time_machine_info = match action {
+            Action::Activate(time_machine_id) => grid
+                .get_entity_from_id("TimeMachine", *time_machine_id)
+                .map(|(_, time_machine)| GridEntityInfo::from(time_machine)),
+            _ => None
+        };
+
+        let time_machine_info = if let Some(time_machine_info) = time_machine_info { time_machine_info } else { continue; };
+
+        let _span = info_span!("time_travel", machine = time_machine_info.id, tick = ticks.0).entered();
+
+        trace!("Grid before the activation: {}", *grid);
+
+        // Recorded so past players click it too
+        grid.add_action(*action);
+        level_stats.count_action(*action);
+
+        let player_index = grid.get_entity_index_from_id("Player", 0);
+        let result = activate_time_machine(&mut grid, &time_machine_info, player_index, ticks.0, &level_info);
+        report_activation(&time_machine_info, &result);
+
+        if let Ok(Activation::TimeTravelled) = result {
+            level_stats.count_time_travel();
+        }
+
+        trace!("Grid after the activation: {}", *grid);
+
+        activated_events.send(TimeMachineActivated { time_machine: time_machine_info, by_player: true, result });
+    }
+}
+
+pub fn report_activation(time_machine_info: &GridEntityInfo, result: &Result<Activation, ActivationRefused>) {
+    match result {
+        Ok(Activation::Blocked) => {
+            info!("Stasis machine {} is blocked, some of its contents are still held", time_machine_info.id);
+        },
+        Ok(activation) => debug!("Time machine {}: {:?}", time_machine_info.id, activation),
+        Err(refused) => info!("{}", refused)
+    }
+}
+
+// activator is the index of the Player or PastPlayer that clicked the machine, None if
+// it went off on its own, in which case it's the player that counts as being inside
+// !!Assumptions!!
+// - If an entity is on the inside of the time machine, no part of it will be on the outside
+pub fn activate_time_machine(
+    grid: &mut Grid,
+    time_machine_info: &GridEntityInfo,
+    activator: Option<usize>,
+    tick: usize,
+    level_info: &LevelInfo
+) -> Result<Activation, ActivationRefused> {
+    let player_index = grid.get_entity_index_from_id("Player", 0).unwrap();
+    let activator = activator.unwrap_or(player_index);
+
+    let contains_activator = grid.tm_contains_index(
+        &grid.get_entity(time_machine_info).unwrap().1,
+        activator
     );
+
+    // A past self inside only sends itself back, the player is the only one whose travel
+    // takes the present along
+    let player_travels = contains_activator && activator == player_index;
+
+    if let Some((_, GridEntity::TimeMachine { kind: TimeMachineKind::Stasis { ticks: stasis_ticks }, .. })) = grid.get_entity(time_machine_info) {
+        let stasis_ticks = *stasis_ticks;
+
+        return toggle_stasis(grid, time_machine_info, stasis_ticks, tick, contains_activator);
+    }
+
     let grid_clone = grid.clone();
     let (
-        start_instance, 
-        id 
-    ) = if let Some((_, GridEntity::TimeMachine { 
+        start_instance,
+        id
+    ) = if let Some((_, GridEntity::TimeMachine {
         start_instance,
         id,
         ..
@@ -38,34 +128,207 @@ pub fn enable_activate_time_machine(
         panic!("Could not find the clicked time machine in the current grid!?!?!?!?");
     };
 
-    if start_instance.is_none() {
-        *start_instance = Some((ticks.0, None, None, grid_clone));
-    } else if !contains_player {
-        *start_instance = None;
+    match start_instance {
+        TimeMachineState::Holding { .. } => {
+            panic!("Only stasis machines can hold their contents");
+        },
+        TimeMachineState::Idle => {
+            start_instance.start_recording(tick, grid_clone);
+
+            Ok(Activation::StartedRecording)
+        },
+        _ if !contains_activator && !level_info.send_without_player => {
+            start_instance.stop();
+
+            grid.remove_contents_of_entity(time_machine_info);
+
+            Ok(Activation::Stopped)
+        },
+        TimeMachineState::Recording { start_tick: start, snapshot: instance_grid } |
+        TimeMachineState::Looping { start, snapshot: instance_grid, .. } => {
+            let mut travelled_grid = instance_grid.clone();
+
+            let time_machine_index = instance_grid.get_entity_index_from_id("TimeMachine", *id).unwrap();
+
+            let duplicated = if player_travels {
+                travelled_grid.replace_time_machine(
+                    grid_clone,
+                    time_machine_index,
+                    *start,
+                    tick,
+                    level_info.duplication_rule
+                )
+            } else {
+                travelled_grid.send_time_machine_contents(
+                    grid_clone,
+                    time_machine_index,
+                    tick,
+                    level_info.duplication_rule
+                )
+            };
+
+            if level_info.duplication_rule == DuplicationRule::Forbid && !duplicated.is_empty() {
+                return Err(ActivationRefused::Duplication(duplicated));
+            }
+
+            let duplicated_keys = travelled_grid.duplicated_keys();
 
-        grid.remove_contents_of_entity(time_machine_info);
+            match level_info.duplication_rule {
+                DuplicationRule::Forbid if !duplicated_keys.is_empty() => {
+                    return Err(ActivationRefused::KeyDuplication(duplicated_keys));
+                },
+                DuplicationRule::Move => travelled_grid.remove_key_copies_outside(time_machine_index, &duplicated_keys),
+                _ => {}
+            }
+
+            *instance_grid = travelled_grid;
+
+            if !player_travels {
+                start_instance.depart(tick);
+
+                return Ok(Activation::SentBack);
+            }
+
+            *grid = instance_grid.clone();
+
+            Ok(Activation::TimeTravelled)
+        }
+    }
+}
+
+// Stasis machines hold their contents on the first activation and release them
+// on the next one, or on their own once the release tick comes if they have one
+fn toggle_stasis(
+    grid: &mut Grid,
+    time_machine_info: &GridEntityInfo,
+    stasis_ticks: usize,
+    tick: usize,
+    contains_activator: bool
+) -> Result<Activation, ActivationRefused> {
+    let time_machine_index = grid.get_entity_index(time_machine_info).unwrap();
+
+    let start_instance = if let Some((_, GridEntity::TimeMachine { start_instance, .. })) = grid.get_entity(time_machine_info) {
+        start_instance.clone()
     } else {
-        let (start,_,  _, instance_grid) = start_instance.as_mut().unwrap();
+        return Ok(Activation::Nothing);
+    };
+
+    match start_instance {
+        TimeMachineState::Idle if contains_activator => Err(ActivationRefused::PlayerInStasis),
+        TimeMachineState::Idle => {
+            grid.hold_time_machine_contents(
+                time_machine_index,
+                if stasis_ticks == 0 { None } else { Some(tick + stasis_ticks) }
+            );
+
+            Ok(Activation::Held)
+        },
+        TimeMachineState::Holding { release_tick: Some(release_tick), .. } if tick < release_tick => Ok(Activation::Nothing),
+        TimeMachineState::Holding { .. } => {
+            // Whatever couldn't get out tries again on the next tick or activation
+            if grid.release_time_machine_contents(time_machine_index) {
+                Ok(Activation::Released)
+            } else {
+                Ok(Activation::Blocked)
+            }
+        },
+        _ => Ok(Activation::Nothing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time_machine(id: usize) -> GridEntityInfo {
+        GridEntityInfo::from(&GridEntity::TimeMachine {
+            start_instance: TimeMachineState::Idle,
+            id,
+            grid: Vec::new(),
+            mask: Vec::new(),
+            pushable: false,
+            kind: TimeMachineKind::Rewind
+        })
+    }
+
+    fn player() -> GridEntityInfo {
+        GridEntityInfo { variant: "Player", ..default() }
+    }
 
-        instance_grid.replace_time_machine(
-            grid_clone, 
-            instance_grid.get_entity_index_from_id("TimeMachine", *id).unwrap(), 
-            *start,
-            ticks.0
-        );
+    #[test]
+    fn contents_go_back_without_the_player() {
+        let level_info = LevelInfo { send_without_player: true, ..default() };
 
-        *grid = instance_grid.clone();
+        // A one cell machine open to the left, with a box in front of it
+        let mut grid = Grid::new_sized(3, 1);
+        grid.add_time_machine((2, 0), 1, 1, 10);
+        grid.add_entity_to_pos(0, 0, &mut player());
+        grid.add_entity_to_pos(1, 0, &mut GridEntityInfo { variant: "Box", id: 20, ..default() });
+
+        if let Some((_, GridEntity::TimeMachine { grid: parts, .. })) = grid.get_entity_from_id_mut("TimeMachine", 10) {
+            parts[0][0].0 = TimeMachinePartType::LeftOpening;
+        }
+
+        let player_index = grid.get_entity_index_from_id("Player", 0);
+
+        assert_eq!(activate_time_machine(&mut grid, &time_machine(10), player_index, 0, &level_info), Ok(Activation::StartedRecording));
+        assert_eq!(grid.try_move_entity(&player(), MoveDirection::Right), Ok(()));
+        assert_eq!(activate_time_machine(&mut grid, &time_machine(10), player_index, 1, &level_info), Ok(Activation::SentBack));
+
+        // The present carries on with the player where they were and nothing duplicated
+        assert_eq!(grid.get_entity_from_id("Player", 0).unwrap().0, (1, 0));
+        assert_eq!(grid.get_all_of_type("Box").len(), 1);
+        assert_eq!(grid.get_entity_from_id("Box", 20).unwrap().0, (2, 0));
+
+        let (_, time_machine) = grid.get_entity_from_id("TimeMachine", 10).unwrap();
+        let start_instance = if let GridEntity::TimeMachine { start_instance, .. } = time_machine { start_instance } else { unreachable!() };
+
+        assert_eq!(start_instance.loop_length(), Some(1));
+
+        // Back then the player hadn't moved yet, and a copy of the box is waiting in the machine
+        let snapshot = start_instance.snapshot().unwrap();
+        let boxes: Vec<(usize, usize)> = snapshot.get_all_of_type("Box").into_iter().map(|(pos, _)| *pos).collect();
+
+        assert_eq!(snapshot.get_entity_from_id("Player", 0).unwrap().0, (0, 0));
+        assert_eq!(boxes, vec![(1, 0), (2, 0)]);
     }
 
+    #[test]
+    fn past_self_in_a_recording_machine_goes_back_instead_of_vanishing() {
+        let level_info = LevelInfo::default();
+
+        // The player starts the recording from outside, then a past self walks in and clicks it
+        let mut grid = Grid::new_sized(4, 1);
+        grid.add_time_machine((3, 0), 1, 1, 10);
+        grid.add_entity_to_pos(0, 0, &mut player());
+        grid.add_entity_to_pos(2, 0, &mut GridEntityInfo { variant: "PastPlayer", id: 30, ..default() });
 
-    // println!("Enable/Activate Time machine (End): {}", *grid);
+        if let Some((_, GridEntity::TimeMachine { grid: parts, .. })) = grid.get_entity_from_id_mut("TimeMachine", 10) {
+            parts[0][0].0 = TimeMachinePartType::LeftOpening;
+        }
 
-    // println!(
-    //     "start_instance: {}\n\n\n", 
-    //     if let Some(info) = &clicked_time_machine.0 {
-    //         if let Some((_, GridEntity::TimeMachine { start_instance, .. })) = grid.get_entity(info) {
-    //             format!("{:?}", start_instance)
-    //         } else { String::new() }
-    //     } else { String::new() }
-    // );
-}
\ No newline at end of file
+        if let Some((_, GridEntity::PastPlayer { actions, .. })) = grid.get_entity_from_id_mut("PastPlayer", 30) {
+            *actions = vec![Action::Move(MoveDirection::Right), Action::Activate(10)];
+        }
+
+        let player_index = grid.get_entity_index_from_id("Player", 0);
+        let past_player_index = grid.get_entity_index_from_id("PastPlayer", 30);
+
+        assert_eq!(activate_time_machine(&mut grid, &time_machine(10), player_index, 0, &level_info), Ok(Activation::StartedRecording));
+
+        let (time_machine_info, activator) = grid.update_events(1).unwrap();
+
+        assert_eq!((time_machine_info.id, activator), (10, past_player_index));
+        assert_eq!(activate_time_machine(&mut grid, &time_machine_info, activator, 1, &level_info), Ok(Activation::SentBack));
+
+        // Still in the machine now, and arriving in it back when the recording started
+        assert_eq!(grid.get_entity_from_id("PastPlayer", 30).unwrap().0, (3, 0));
+        assert_eq!(grid.get_entity_from_id("Player", 0).unwrap().0, (0, 0));
+
+        let (_, time_machine) = grid.get_entity_from_id("TimeMachine", 10).unwrap();
+        let snapshot = if let GridEntity::TimeMachine { start_instance, .. } = time_machine { start_instance.snapshot().unwrap() } else { unreachable!() };
+        let past_players: Vec<(usize, usize)> = snapshot.get_all_of_type("PastPlayer").into_iter().map(|(pos, _)| *pos).collect();
+
+        assert_eq!(past_players, vec![(2, 0), (3, 0)]);
+    }
+}
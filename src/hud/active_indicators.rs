@@ -0,0 +1,102 @@
+use super::*;
+
+pub fn update_active_indicators(
+    mut commands: Commands,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    asset_server: Res<AssetServer>,
+    ldtk_level: Query<Entity, With<Handle<LdtkLevel>>>,
+    mut indicators_query: Query<(&ActiveIndicator, &mut TextureAtlasSprite, &mut Transform, &mut Visibility)>,
+    grid: Res<Grid>,
+    ticks: Res<Ticks>,
+    time: Res<Time>
+) {
+    let level_entity = if let Ok(level_entity) = ldtk_level.get_single() { level_entity } else { return; };
+
+    let mut unlinked: Vec<(usize, (usize, usize), &Vec<Vec<bool>>)> = grid
+        .entities_iter()
+        .filter_map(|(corner, grid_entity)| match grid_entity {
+            GridEntity::TimeMachine { id, mask, .. } => Some((*id, *corner, mask)),
+            _ => None
+        })
+        .collect();
+
+    let blink_on = ((time.elapsed_seconds() * 4.0) as usize).is_multiple_of(2);
+
+    for (indicator, mut sprite, mut transform, mut visibility) in indicators_query.iter_mut() {
+        let machine = indicator.machine_id.and_then(|id| grid.get_entity_from_id("TimeMachine", id));
+
+        let (corner, start_instance) = match machine {
+            Some((corner, GridEntity::TimeMachine { start_instance, .. })) => (corner, start_instance),
+            // The machine isn't in the grid at the moment
+            _ => {
+                visibility.is_visible = false;
+                continue;
+            }
+        };
+
+        unlinked.retain(|(id, ..)| Some(*id) != indicator.machine_id);
+
+        visibility.is_visible = true;
+
+        transform.translation.x = (corner.0 * TILE_SIZE) as f32 + indicator.offset.x;
+        transform.translation.y = (corner.1 * TILE_SIZE) as f32 + indicator.offset.y;
+
+        let upcoming = start_instance.next_departure().or(start_instance.release_tick());
+        let about_to_depart = upcoming.is_some_and(|departure| {
+            departure >= ticks.0 && departure - ticks.0 <= DEPARTURE_WARNING_TICKS
+        });
+
+        sprite.color = match start_instance {
+            _ if about_to_depart && blink_on => Color::YELLOW,
+            _ if about_to_depart => Color::rgba(1.0, 1.0, 0.0, 0.25),
+            TimeMachineState::Idle => Color::rgba(1.0, 1.0, 1.0, 0.4),
+            TimeMachineState::Recording { .. } => Color::RED,
+            TimeMachineState::Looping { .. } => Color::rgb(0.3, 0.5, 1.0),
+            TimeMachineState::Holding { .. } => Color::rgb(0.6, 0.2, 0.9)
+        };
+    }
+
+    // Machines placed without an indicator, or made by time travel, get a small one
+    // in the top left of their top left part
+    for (id, corner, mask) in unlinked {
+        let part = mask
+            .iter()
+            .enumerate()
+            .flat_map(|(x, column)| column
+                .iter()
+                .enumerate()
+                .filter(|(_, filled)| **filled)
+                .map(move |(y, _)| (x, y))
+            )
+            .min_by_key(|(x, y)| (*x, usize::MAX - *y))
+            .unwrap_or((0, 0));
+
+        let offset = Vec2::new(
+            (part.0 * TILE_SIZE + TILE_SIZE / 8) as f32,
+            ((part.1 + 1) * TILE_SIZE - TILE_SIZE / 8) as f32
+        );
+
+        commands.entity(level_entity).with_children(|parent| {
+            parent.spawn((
+                ActiveIndicator {
+                    machine_iid: None,
+                    machine_id: Some(id),
+                    offset
+                },
+                SpriteSheetBundle {
+                    sprite: TextureAtlasSprite {
+                        index: 23,
+                        ..Default::default()
+                    },
+                    texture_atlas: tileset_atlas(&mut texture_atlases, &asset_server),
+                    transform: Transform::from_xyz(
+                        (corner.0 * TILE_SIZE) as f32 + offset.x,
+                        (corner.1 * TILE_SIZE) as f32 + offset.y,
+                        10.0
+                    ).with_scale(Vec3::splat(0.25)),
+                    ..Default::default()
+                }
+            ));
+        });
+    }
+}
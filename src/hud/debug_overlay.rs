@@ -0,0 +1,77 @@
+use super::*;
+
+// F3 shows the grid the way the game sees it, it's hidden until then
+#[derive(Resource, Default)]
+pub struct DebugOverlay {
+    pub visible: bool
+}
+
+#[derive(Component)]
+pub struct DebugOverlayText;
+
+pub fn spawn_debug_overlay(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle {
+            font_size: 14.0,
+            ..hud_text_style(&asset_server, Color::WHITE)
+        })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(50.0),
+                    left: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        DebugOverlayText
+    ))
+    .insert(BackgroundColor(Color::rgba(0.0, 0.0, 0.0, 0.75)))
+    .insert(Visibility { is_visible: false });
+}
+
+pub fn toggle_debug_overlay(
+    input: Res<Input<KeyCode>>,
+    mut debug_overlay: ResMut<DebugOverlay>
+) {
+    if input.just_pressed(KeyCode::F3) {
+        debug_overlay.visible = !debug_overlay.visible;
+    }
+}
+
+pub fn update_debug_overlay(
+    grid: Res<Grid>,
+    ticks: Res<Ticks>,
+    debug_overlay: Res<DebugOverlay>,
+    mut overlay_query: Query<(&mut Text, &mut Visibility), With<DebugOverlayText>>
+) {
+    let (mut text, mut visibility) = if let Ok(overlay) = overlay_query.get_single_mut() { overlay } else { return; };
+
+    // Only written when it flips so the Visibility doesn't count as changed every frame
+    if visibility.is_visible != debug_overlay.visible {
+        visibility.is_visible = debug_overlay.visible;
+    }
+
+    if !debug_overlay.visible || !(debug_overlay.is_changed() || grid.is_changed() || ticks.is_changed()) { return; }
+
+    // Top row first so it lines up with the level, walls are ##
+    let indices = (0..grid.height()).rev().fold(String::new(), |string, y| {
+        format!("{}\n {}", string, (0..grid.width()).fold(String::new(), |row, x| {
+            if grid.is_wall(x, y) { format!("{} ##", row) } else { format!("{} {:2}", row, grid.grid_index(x, y)) }
+        }))
+    });
+
+    // Time machines print their own grids, so the nested ones show up here too
+    let entities = grid
+        .entities_iter()
+        .enumerate()
+        .filter(|(_, (_, grid_entity))| grid_entity != &GridEntity::None)
+        .fold(String::new(), |string, (i, (corner, grid_entity))| {
+            format!("{}\n [{}] @ {:?} -> {}", string, i, corner, grid_entity)
+        });
+
+    text.sections[0].value = format!("Tick {}  (F3 to hide)\nGrid:{}\nEntities:{}", ticks.0, indices, entities);
+}
@@ -0,0 +1,86 @@
+use super::*;
+
+#[derive(Resource)]
+pub struct GhostPreview {
+    pub enabled: bool,
+    pub steps: usize
+}
+
+impl Default for GhostPreview {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            steps: 5
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Ghost;
+
+pub fn toggle_ghost_preview(
+    input: Res<Input<KeyCode>>,
+    mut ghost_preview: ResMut<GhostPreview>
+) {
+    if input.just_pressed(KeyCode::G) {
+        ghost_preview.enabled = !ghost_preview.enabled;
+    }
+}
+
+pub fn update_ghost_preview(
+    mut commands: Commands,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    asset_server: Res<AssetServer>,
+    ldtk_level: Query<Entity, With<Handle<LdtkLevel>>>,
+    ghosts_query: Query<Entity, With<Ghost>>,
+    ghost_preview: Res<GhostPreview>,
+    grid: Res<Grid>,
+    ticks: Res<Ticks>
+) {
+    if !grid.is_changed() && !ghost_preview.is_changed() { return; }
+
+    for ghost in ghosts_query.iter() {
+        commands.entity(ghost).despawn_recursive();
+    }
+
+    let level_entity = if let (true, Ok(level_entity)) = (ghost_preview.enabled, ldtk_level.get_single()) {
+        level_entity
+    } else {
+        return;
+    };
+
+    let texture_atlas = tileset_atlas(&mut texture_atlases, &asset_server);
+
+    for (id, path) in grid.preview_past_players(ticks.0, ghost_preview.steps) {
+        let mut last_pos = grid.get_entity_from_id("PastPlayer", id).map(|(pos, _)| *pos);
+
+        for (step, pos) in path.into_iter().enumerate() {
+            // Waiting doesn't need a new ghost
+            if Some(pos) == last_pos { continue; }
+            last_pos = Some(pos);
+
+            // Further away steps fade out more
+            let alpha = 0.6 * (1.0 - step as f32 / ghost_preview.steps as f32) + 0.1;
+
+            commands.entity(level_entity).with_children(|parent| {
+                parent.spawn((
+                    SpriteSheetBundle {
+                        sprite: TextureAtlasSprite {
+                            index: 50,
+                            color: Color::rgba(1.0, 1.0, 1.0, alpha),
+                            ..Default::default()
+                        },
+                        texture_atlas: texture_atlas.clone(),
+                        transform: Transform::from_xyz(
+                            (TILE_SIZE * pos.0 + TILE_SIZE / 2) as f32,
+                            (TILE_SIZE * pos.1 + TILE_SIZE / 2) as f32,
+                            1.0
+                        ),
+                        ..Default::default()
+                    },
+                    Ghost
+                ));
+            });
+        }
+    }
+}
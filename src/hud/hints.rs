@@ -0,0 +1,171 @@
+use super::*;
+
+// Grids the hint search looks at per frame
+pub const HINT_NODES_PER_FRAME: usize = 50;
+pub const MAX_HINT_DETAIL: usize = 3;
+
+// Each press of H asks for more detail: which machine to use, then where to use
+// it from, then the exact next action
+#[derive(Resource, Default)]
+pub struct Hints {
+    pub search: Option<HintSearch>,
+    pub detail: usize
+}
+
+#[derive(Component)]
+pub struct HintText;
+
+#[derive(Component)]
+pub struct HintMarker;
+
+pub fn spawn_hints(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.spawn((
+        TextBundle::from_section("", hud_text_style(&asset_server, Color::CYAN))
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        HintText
+    ));
+}
+
+// Hints are for the grid they were asked on, anything happening makes them stale
+pub fn reset_hints(grid: Res<Grid>, mut hints: ResMut<Hints>) {
+    if grid.is_changed() && (hints.search.is_some() || hints.detail != 0) {
+        *hints = Hints::default();
+    }
+}
+
+pub fn request_hint(
+    input: Res<Input<KeyCode>>,
+    grid: Res<Grid>,
+    ticks: Res<Ticks>,
+    level_info: Res<LevelInfo>,
+    mut hints: ResMut<Hints>
+) {
+    if !input.just_pressed(KeyCode::H) { return; }
+
+    hints.detail = (hints.detail + 1).min(MAX_HINT_DETAIL);
+
+    if let (None, Some(goal)) = (&hints.search, level_info.goal) {
+        // The level's par is how far ahead the search looks
+        let max_ticks = level_info.par_moves.unwrap_or(DEFAULT_SEARCH_TICKS);
+
+        hints.search = Some(HintSearch::new(&grid, ticks.0, goal, max_ticks, &level_info));
+    }
+}
+
+pub fn run_hint_search(mut hints: ResMut<Hints>) {
+    // Checked first so hints only count as changed while there's searching to do
+    let searching = hints.search
+        .as_ref()
+        .is_some_and(|search| search.status == SearchStatus::Searching);
+
+    if !searching { return; }
+
+    if let Some(search) = &mut hints.search {
+        search.step(HINT_NODES_PER_FRAME);
+    }
+}
+
+pub fn update_hint_text(
+    hints: Res<Hints>,
+    level_info: Res<LevelInfo>,
+    mut hint_text_query: Query<&mut Text, With<HintText>>
+) {
+    if !hints.is_changed() { return; }
+
+    let mut text = if let Ok(text) = hint_text_query.get_single_mut() { text } else { return; };
+
+    text.sections[0].value = match (&hints.search, hints.detail) {
+        (_, 0) => String::new(),
+        (None, _) if level_info.goal.is_none() => "This level has no goal to give hints for".to_string(),
+        (None, _) => String::new(),
+        (Some(search), detail) => match &search.status {
+            SearchStatus::Searching => "Thinking...".to_string(),
+            SearchStatus::NotFound => "There's no way to the goal from here, time to start over".to_string(),
+            SearchStatus::GaveUp => "Couldn't work out a hint from here".to_string(),
+            SearchStatus::Found(path) => describe_hint(path, detail)
+        }
+    };
+}
+
+fn describe_hint(path: &[SolutionStep], detail: usize) -> String {
+    let first_activation = path.iter().find_map(|(action, pos)| match action {
+        Action::Activate(id) => Some((*id, *pos)),
+        _ => None
+    });
+
+    match (detail, first_activation, path.first()) {
+        (_, _, None) => "You're already there!".to_string(),
+        (1, Some((id, _)), _) => format!("Try time machine {}", id),
+        (1, None, _) => "No time machine needed, head for the goal".to_string(),
+        (2, Some((id, _)), _) => format!("Use time machine {} from the marked cell", id),
+        (2, None, _) => format!("The goal is {} ticks away", path.iter().filter(|(action, _)| action.takes_tick()).count()),
+        (_, _, Some((action, _))) => match action {
+            Action::Move(direction) => format!("Next: move {}", match direction {
+                MoveDirection::Up => "up",
+                MoveDirection::Down => "down",
+                MoveDirection::Left => "left",
+                MoveDirection::Right => "right"
+            }),
+            Action::Wait => "Next: wait".to_string(),
+            Action::Activate(id) => format!("Next: use time machine {}", id)
+        }
+    }
+}
+
+// Shows where to stand once the hint is detailed enough
+pub fn update_hint_marker(
+    mut commands: Commands,
+    ldtk_level: Query<Entity, With<Handle<LdtkLevel>>>,
+    markers_query: Query<Entity, With<HintMarker>>,
+    hints: Res<Hints>
+) {
+    if !hints.is_changed() { return; }
+
+    for marker in markers_query.iter() {
+        commands.entity(marker).despawn_recursive();
+    }
+
+    let stand_at = match (&hints.search, hints.detail >= 2) {
+        (Some(HintSearch { status: SearchStatus::Found(path), .. }), true) => path
+            .iter()
+            .find(|(action, _)| matches!(action, Action::Activate(_)))
+            .map(|(_, pos)| *pos),
+        _ => None
+    };
+
+    let (level_entity, pos) = if let (Ok(level_entity), Some(pos)) = (ldtk_level.get_single(), stand_at) {
+        (level_entity, pos)
+    } else {
+        return;
+    };
+
+    commands.entity(level_entity).with_children(|parent| {
+        parent.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(0.0, 1.0, 1.0, 0.3),
+                    custom_size: Some(Vec2::splat(TILE_SIZE as f32)),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(
+                    (TILE_SIZE * pos.0 + TILE_SIZE / 2) as f32,
+                    (TILE_SIZE * pos.1 + TILE_SIZE / 2) as f32,
+                    0.5
+                ),
+                ..Default::default()
+            },
+            HintMarker
+        ));
+    });
+}
@@ -0,0 +1,119 @@
+use super::*;
+
+#[derive(Component)]
+pub struct LevelInfoText;
+
+#[derive(Component)]
+pub struct GoalMarker;
+
+pub fn spawn_level_info(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new("", hud_text_style(&asset_server, Color::WHITE)),
+            TextSection::new("", hud_text_style(&asset_server, Color::GRAY)),
+            TextSection::new("", hud_text_style(&asset_server, Color::WHITE)),
+            TextSection::new("", hud_text_style(&asset_server, Color::YELLOW))
+        ])
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        LevelInfoText
+    ));
+}
+
+pub fn update_level_info(
+    level_info: Res<LevelInfo>,
+    level_stats: Res<LevelStats>,
+    best_results: Res<BestResults>,
+    mut level_info_query: Query<&mut Text, With<LevelInfoText>>
+) {
+    if !level_info.is_changed() && !level_stats.is_changed() { return; }
+
+    let mut text = if let Ok(text) = level_info_query.get_single_mut() { text } else { return; };
+
+    let par = match (level_info.par_moves, level_info.par_time_travels) {
+        (Some(moves), Some(time_travels)) => format!("\nPar: {} moves, {} time travels", moves, time_travels),
+        (Some(moves), None) => format!("\nPar: {} moves", moves),
+        (None, Some(time_travels)) => format!("\nPar: {} time travels", time_travels),
+        (None, None) => String::new()
+    };
+
+    text.sections[0].value = format!("{}{}", level_info.name, par);
+    text.sections[1].value = level_info.hint
+        .as_ref()
+        .map_or(String::new(), |hint| format!("\n{}", hint));
+
+    text.sections[2].value = format!(
+        "\nMoves {}  Waits {}  Activations {}  Time travels {}  {:.0}s",
+        level_stats.moves,
+        level_stats.waits,
+        level_stats.activations,
+        level_stats.time_travels,
+        level_stats.time.as_secs_f32()
+    );
+
+    let finished = level_stats.result.map_or(String::new(), |result| {
+        format!("Finished! {}  ", star_rating(result.stars))
+    });
+    let best = best_results.0.get(&level_info.identifier).map_or(String::new(), |best| {
+        format!("Best {} in {} moves", star_rating(best.stars), best.moves + best.waits)
+    });
+
+    text.sections[3].value = if finished.is_empty() && best.is_empty() {
+        String::new()
+    } else {
+        format!("\n{}{}", finished, best)
+    };
+}
+
+fn star_rating(stars: usize) -> String {
+    format!("{}{}", "★".repeat(stars), "☆".repeat(LevelResult::MAX_STARS.saturating_sub(stars)))
+}
+
+// Marks the goal cell, it gets respawned whenever a level is set up
+pub fn update_goal_marker(
+    mut commands: Commands,
+    ldtk_level: Query<Entity, With<Handle<LdtkLevel>>>,
+    markers_query: Query<Entity, With<GoalMarker>>,
+    level_info: Res<LevelInfo>
+) {
+    if !level_info.is_changed() { return; }
+
+    for marker in markers_query.iter() {
+        commands.entity(marker).despawn_recursive();
+    }
+
+    let (level_entity, goal) = if let (Ok(level_entity), Some(goal)) = (ldtk_level.get_single(), level_info.goal) {
+        (level_entity, goal)
+    } else {
+        return;
+    };
+
+    commands.entity(level_entity).with_children(|parent| {
+        parent.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(1.0, 0.85, 0.0, 0.3),
+                    custom_size: Some(Vec2::splat(TILE_SIZE as f32)),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(
+                    (TILE_SIZE * goal.0 + TILE_SIZE / 2) as f32,
+                    (TILE_SIZE * goal.1 + TILE_SIZE / 2) as f32,
+                    0.5
+                ),
+                ..Default::default()
+            },
+            GoalMarker
+        ));
+    });
+}
@@ -0,0 +1,58 @@
+use super::*;
+
+#[derive(Component)]
+pub struct LineageText;
+
+pub fn spawn_lineage(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.spawn((
+        TextBundle::from_section("", hud_text_style(&asset_server, Color::WHITE))
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        LineageText
+    ));
+}
+
+// Lists everything time travel duplicated together with where it came from
+pub fn update_lineage(
+    grid: Res<Grid>,
+    mut lineage_query: Query<&mut Text, With<LineageText>>
+) {
+    if !grid.is_changed() { return; }
+
+    let mut text = if let Ok(text) = lineage_query.get_single_mut() { text } else { return; };
+
+    text.sections[0].value = grid.lineage()
+        .iter()
+        .map(|&(clone, _)| {
+            grid.ancestors_of(clone)
+                .into_iter()
+                .fold(describe_entity(&grid, clone), |line, ancestor| {
+                    format!("{} <- {}", line, describe_entity(&grid, ancestor))
+                })
+        })
+        .fold(String::new(), |text, line| format!("{}{}\n", text, line));
+}
+
+fn describe_entity(grid: &Grid, (variant, id): (&'static str, usize)) -> String {
+    let name = match variant {
+        "PastPlayer" => "Past player",
+        "TimeMachine" => "Machine",
+        variant => variant
+    };
+
+    if grid.get_entity_from_id(variant, id).is_some() {
+        format!("{} {}", name, id)
+    } else {
+        format!("({} {} gone)", name, id)
+    }
+}
@@ -0,0 +1,97 @@
+pub use super::*;
+use iyes_loopless::prelude::*;
+
+mod timeline;
+pub use timeline::*;
+
+mod ghost_preview;
+pub use ghost_preview::*;
+
+mod lineage;
+pub use lineage::*;
+
+mod active_indicators;
+pub use active_indicators::*;
+
+mod level_info;
+pub use level_info::*;
+
+mod hints;
+pub use hints::*;
+
+mod debug_overlay;
+pub use debug_overlay::*;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(GhostPreview::default())
+            .insert_resource(Hints::default())
+            .add_startup_system(spawn_timeline)
+            .add_startup_system(spawn_lineage)
+            .add_startup_system(spawn_level_info)
+            .add_startup_system(spawn_hints)
+            .add_system(
+                update_timeline
+                .after("update to grid")
+            )
+            .add_system(
+                update_lineage
+                .after("update to grid")
+            )
+            .add_system(update_level_info)
+            .add_system(
+                reset_hints
+                .label("reset hints")
+                .after("update to grid")
+            )
+            .add_system(
+                request_hint
+                .label("request hint")
+                .after("reset hints")
+            )
+            .add_system(
+                run_hint_search
+                .label("run hint search")
+                .after("request hint")
+            )
+            .add_system(update_hint_text.after("run hint search"))
+            .add_system(update_hint_marker.after("run hint search"))
+            .add_system(update_goal_marker)
+            .add_system(
+                update_active_indicators
+                .run_if(level_setup_completed)
+                .after("update to grid")
+            )
+            .add_system(toggle_ghost_preview.label("toggle ghost preview"))
+            .add_system(
+                update_ghost_preview
+                .after("toggle ghost preview")
+                .after("update to grid")
+            )
+        ;
+
+        // The overlay is for working on the game, players don't get it
+        if cfg!(debug_assertions) {
+            app
+                .insert_resource(DebugOverlay::default())
+                .add_startup_system(spawn_debug_overlay)
+                .add_system(toggle_debug_overlay.label("toggle debug overlay"))
+                .add_system(
+                    update_debug_overlay
+                    .after("toggle debug overlay")
+                    .after("update to grid")
+                );
+        }
+    }
+}
+
+pub fn hud_text_style(asset_server: &AssetServer, color: Color) -> TextStyle {
+    TextStyle {
+        font: asset_server.load("fonts/DejaVuSansMono.ttf"),
+        font_size: 22.0,
+        color
+    }
+}
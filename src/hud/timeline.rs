@@ -0,0 +1,129 @@
+use super::*;
+
+// How many ticks before a departure the countdown starts showing
+pub const DEPARTURE_WARNING_TICKS: usize = 3;
+
+// How many ticks the timeline bar shows
+const TIMELINE_LENGTH: usize = 30;
+
+#[derive(Component)]
+pub struct TimelineText;
+
+pub fn spawn_timeline(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.spawn((
+        TextBundle::from_section("", hud_text_style(&asset_server, Color::WHITE))
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        TimelineText
+    ));
+}
+
+pub fn update_timeline(
+    grid: Res<Grid>,
+    ticks: Res<Ticks>,
+    asset_server: Res<AssetServer>,
+    mut timeline_query: Query<&mut Text, With<TimelineText>>
+) {
+    if !grid.is_changed() && !ticks.is_changed() { return; }
+
+    let mut text = if let Ok(text) = timeline_query.get_single_mut() { text } else { return; };
+    let normal_style = hud_text_style(&asset_server, Color::WHITE);
+    let warning_style = hud_text_style(&asset_server, Color::RED);
+
+    // The bar scrolls so that the current tick is always on it
+    let first_tick = ticks.0.saturating_sub(TIMELINE_LENGTH / 2);
+
+    text.sections = vec![TextSection::new(
+        format!("Tick {}\n", ticks.0),
+        normal_style.clone()
+    )];
+
+    let mut time_machines: Vec<&((usize, usize), GridEntity)> = grid.get_all_of_type("TimeMachine");
+    time_machines.sort_by_key(|(_, time_machine)| {
+        if let GridEntity::TimeMachine { id, .. } = time_machine { *id } else { 0 }
+    });
+
+    for (_, time_machine) in time_machines {
+        let (id, start_instance) = if let GridEntity::TimeMachine { id, start_instance, .. } = time_machine {
+            (id, start_instance)
+        } else {
+            continue;
+        };
+
+        let (description, bar) = match start_instance {
+            TimeMachineState::Idle => (
+                "idle".to_string(),
+                timeline_bar(first_tick, ticks.0, None, None)
+            ),
+            TimeMachineState::Holding { release_tick: Some(release_tick), held } => (
+                format!("holding {} until {}", held.len(), release_tick),
+                timeline_bar(first_tick, ticks.0, None, Some(*release_tick))
+            ),
+            TimeMachineState::Holding { release_tick: None, held } => (
+                format!("holding {} until activated", held.len()),
+                timeline_bar(first_tick, ticks.0, None, None)
+            ),
+            TimeMachineState::Recording { start_tick, .. } => (
+                format!("recording since {}", start_tick),
+                timeline_bar(first_tick, ticks.0, Some((*start_tick, ticks.0)), None)
+            ),
+            TimeMachineState::Looping { start, length, next_departure, .. } => (
+                format!(
+                    "loop {}..{} ({} ticks), departs at {}",
+                    start,
+                    start + length,
+                    length,
+                    next_departure
+                ),
+                timeline_bar(first_tick, ticks.0, Some((*start, start + length)), Some(*next_departure))
+            )
+        };
+
+        text.sections.push(TextSection::new(
+            format!("Machine {:2} {} {}", id, bar, description),
+            normal_style.clone()
+        ));
+
+        let upcoming = start_instance.next_departure()
+            .map(|departure| ("departing", departure))
+            .or(start_instance.release_tick().map(|release_tick| ("releasing", release_tick)));
+
+        if let Some((action, departure)) = upcoming {
+            if departure >= ticks.0 && departure - ticks.0 <= DEPARTURE_WARNING_TICKS {
+                text.sections.push(TextSection::new(
+                    format!("  {} in {}!", action, departure - ticks.0),
+                    warning_style.clone()
+                ));
+            }
+        }
+
+        text.sections.push(TextSection::new("\n", normal_style.clone()));
+    }
+}
+
+// | is the current tick, = is the recorded range and > is the departure or release
+fn timeline_bar(first_tick: usize, now: usize, recording: Option<(usize, usize)>, departure: Option<usize>) -> String {
+    let bar: String = (first_tick..first_tick + TIMELINE_LENGTH).map(|t| {
+        if t == now {
+            '|'
+        } else if Some(t) == departure {
+            '>'
+        } else if recording.is_some_and(|(start, end)| start <= t && t < end) {
+            '='
+        } else {
+            '.'
+        }
+    }).collect();
+
+    format!("[{}]", bar)
+}
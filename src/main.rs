@@ -1,4 +1,7 @@
-use bevy::{prelude::*, window::close_on_esc};
+// Bevy systems take a resource or query per thing they look at, so these are the norm
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use bevy::{prelude::*, window::close_on_esc, log::LogPlugin};
 use bevy_ecs_ldtk::prelude::*;
 // use iyes_loopless::prelude::*;
 use crate::prelude::*;
@@ -10,6 +13,7 @@ pub enum AppState {
     MainMenu,
     InGame,
     Paused,
+    Editor
 }
 
 fn main() {
@@ -21,7 +25,22 @@ fn main() {
             },
             ..Default::default()
         })
-        .set(ImagePlugin::default_nearest());
+        .set(AssetPlugin {
+            // Lets levels get edited in LDtk while the game is running
+            watch_for_changes: cfg!(debug_assertions),
+            ..Default::default()
+        })
+        .set(ImagePlugin::default_nearest())
+        .set(LogPlugin {
+            // The game's own logs are only warnings and errors in release builds,
+            // RUST_LOG overrides this, e.g. RUST_LOG=time_game::events_plugin=trace
+            filter: if cfg!(debug_assertions) {
+                "wgpu=error,time_game=debug".to_string()
+            } else {
+                "wgpu=error,time_game=warn".to_string()
+            },
+            ..Default::default()
+        });
 
     App::new()
         .add_plugins(default_plugins)
@@ -29,11 +48,16 @@ fn main() {
 
         .add_plugin(LevelSetupPlugin)
         .add_plugin(EventsPlugin)
+        .add_plugin(HudPlugin)
+        .add_plugin(EditorPlugin)
 
         .insert_resource(LevelWindowInfo { scaling_factor: 1.0, offset: (0.0, 0.0) })
         .insert_resource(Ticks(0))
         .insert_resource(Dims {x: 0, y: 0})
         .insert_resource(Grid::new())
+        .insert_resource(LevelInfo::default())
+        .insert_resource(LevelStats::default())
+        .insert_resource(BestResults::load())
 
         .insert_resource(ClearColor(Color::hex("1E2B39").unwrap()))
         .insert_resource(LevelSelection::Index(2))
@@ -44,12 +68,16 @@ fn main() {
         .register_ldtk_entity::<PlayerBundle>("Player")
         .register_ldtk_entity::<PastPlayerBundle>("PastPlayer")
         .register_ldtk_entity::<BoxBundle>("Box")
+        .register_ldtk_entity::<PlateBundle>("Plate")
+        .register_ldtk_entity::<DoorBundle>("Door")
+        .register_ldtk_entity::<KeyBundle>("Key")
+        .register_ldtk_entity::<LockBundle>("Lock")
         .register_ldtk_entity::<TimeMachinePartBundle>("Time_machine_part")
+        .register_ldtk_entity::<ActiveIndicatorBundle>("ActiveIndicator")
+        .register_behaviour::<TimedGate>("Timed_gate")
 
-        .add_system_set(
-            SystemSet::on_enter(AppState::InGame)
-            .with_system(setup)
-        )
+        // Only once, going between the editor and the game shouldn't spawn the level again
+        .add_startup_system(setup)
         .add_system(close_on_esc)
         .add_state(AppState::InGame) // Change later
         .run();
@@ -59,9 +87,11 @@ pub mod setup;
 pub mod entities;
 pub mod events_plugin;
 pub mod resources;
+pub mod hud;
+pub mod editor;
 
 pub mod prelude {
     pub use super::{
-        setup::*, entities::*, resources::*, events_plugin::*
+        setup::*, entities::*, resources::*, events_plugin::*, hud::*, editor::*
     };
 }
\ No newline at end of file
@@ -0,0 +1,35 @@
+use super::*;
+
+// What a player did, recorded so that past players can do it again.
+// Moves and waits take up a tick, activations happen right after the tick they follow
+#[derive(Clone, Copy, Debug, PartialEq, Hash)]
+pub enum Action {
+    Move(MoveDirection),
+    Wait,
+    // id of the time machine that got clicked
+    Activate(usize)
+}
+
+impl Action {
+    pub fn takes_tick(&self) -> bool {
+        !matches!(self, Action::Activate(_))
+    }
+
+    // The actions done after tick, leaving out the activations that happened during it
+    pub fn after_tick(actions: &[Action], tick: usize) -> Vec<Action> {
+        let mut ticks_passed = 0;
+
+        actions
+            .iter()
+            .skip_while(|action| {
+                if ticks_passed < tick {
+                    if action.takes_tick() { ticks_passed += 1; }
+                    true
+                } else {
+                    !action.takes_tick()
+                }
+            })
+            .copied()
+            .collect()
+    }
+}
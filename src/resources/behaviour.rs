@@ -0,0 +1,57 @@
+use super::*;
+
+// Puzzle elements that live outside of the GridEntity enum. Implementing this and calling
+// app.register_behaviour::<T>("LdtkIdentifier") is all a new entity type needs, the grid
+// keeps it in GridEntity::Custom and asks it everything it would otherwise match on
+pub trait EntityBehaviour: Send + Sync + 'static {
+    // Built from the LDtk entity when the level gets set up
+    fn from_instance(entity_instance: &EntityInstance) -> Self where Self: Sized;
+    // The other way around, the fields the editor writes into the LDtk file when it saves
+    fn to_field_instances(&self) -> Vec<(&'static str, serde_json::Value)> { Vec::new() }
+
+    // What GridEntityInfo::variant is for it, the editor saves it as the LDtk entity of that name
+    fn variant(&self) -> &'static str;
+    fn id(&self) -> usize;
+    fn set_id(&mut self, id: usize);
+
+    // Floor entities lie under everything else like plates and doors do,
+    // the rest take up their cell like boxes
+    fn is_floor(&self) -> bool { false }
+    // Only asked of floor entities, if true nothing can move onto it right now
+    fn is_blocking(&self) -> bool { false }
+    fn is_pushable(&self) -> bool { true }
+    fn presses_plates(&self) -> bool { !self.is_floor() }
+
+    // Runs every tick after past players have moved, pos is where it is
+    fn update(&mut self, _pos: (usize, usize), _tick: usize, _grid: &Grid) {}
+
+    // Index into tileset_alt.png and the tint it gets drawn with
+    fn tile_index(&self) -> usize;
+    fn color(&self) -> Color { Color::WHITE }
+
+    // Its state in one line, two entities that serialize the same are the same
+    fn serialize(&self) -> String;
+    fn clone_box(&self) -> std::boxed::Box<dyn EntityBehaviour>;
+}
+
+// Also the component the bevy entity carries so setup_grid can hand it to the grid
+#[derive(Component)]
+pub struct CustomEntity(pub std::boxed::Box<dyn EntityBehaviour>);
+
+impl Clone for CustomEntity {
+    fn clone(&self) -> Self {
+        Self(self.0.clone_box())
+    }
+}
+
+impl PartialEq for CustomEntity {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.variant() == other.0.variant() && self.0.serialize() == other.0.serialize()
+    }
+}
+
+impl std::fmt::Debug for CustomEntity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({})", self.0.variant(), self.0.serialize())
+    }
+}
@@ -3,30 +3,58 @@ use super::*;
 #[derive(Default, Debug, PartialEq, Clone)]
 pub enum GridEntity {
     Player {
-        movements: Vec<IVec2>
+        actions: Vec<Action>,
+        inventory: Inventory
     },
     PastPlayer {
         id: usize, 
-        movements: Vec<IVec2>
+        actions: Vec<Action>,
+        inventory: Inventory
     },
     Box {
         id: usize
     },
     TimeMachine {
-        start_instance: Option<(usize, Option<usize>, Option<usize>, Grid)>,
+        start_instance: TimeMachineState,
         id: usize, 
-        grid: Vec<Vec<(TimeMachinePartType, usize)>>
+        grid: Vec<Vec<(TimeMachinePartType, usize)>>,
+        // Which cells of grid are actually part of the time machine, the
+        // rest is just padding so that non-rectangular shapes fit in grid
+        mask: Vec<Vec<bool>>,
+        pushable: bool,
+        kind: TimeMachineKind
     },
+    // Plates and doors lie on the floor, they aren't in entity_grid so things can stand on them
+    Plate {
+        id: usize,
+        active: bool
+    },
+    Door {
+        id: usize,
+        // ids of the plates that all have to be active for the door to open
+        plates: Vec<usize>,
+        open: bool
+    },
+    // Picked up by players walking onto it
+    Key {
+        id: usize
+    },
+    // Blocks everything until a player with a key walks into it
+    Lock {
+        id: usize
+    },
+    // Anything added with register_behaviour
+    Custom(CustomEntity),
     #[default]
     None
 }
 
 impl GridEntity {
     fn is_tm(&self) -> bool {
-        if let GridEntity::TimeMachine { .. } = self { true } else { false }
+        matches!(self, GridEntity::TimeMachine { .. })
     }
         
-    fn get_tm_grid<'a>(&'a self) -> &'a Vec<Vec<(TimeMachinePartType, usize)>> {
+    fn get_tm_grid(&self) -> &Vec<Vec<(TimeMachinePartType, usize)>> {
         if let GridEntity::TimeMachine{ grid, .. } = self {
             grid
         } else {
@@ -34,7 +62,7 @@ impl GridEntity {
         }
     }
     
-    fn get_tm_grid_mut<'a>(&'a mut self) -> &'a mut Vec<Vec<(TimeMachinePartType, usize)>> {
+    fn get_tm_grid_mut(&mut self) -> &mut Vec<Vec<(TimeMachinePartType, usize)>> {
         if let GridEntity::TimeMachine{ grid, .. } = self {
             grid
         } else {
@@ -42,10 +70,58 @@ impl GridEntity {
         }
     }
 
+    // Relative positions of the cells that are part of the time machine
+    pub fn tm_cells(&self) -> Vec<(usize, usize)> {
+        if let GridEntity::TimeMachine { mask, .. } = self {
+            mask.iter().enumerate().flat_map(|(i, column)| {
+                column.iter().enumerate().filter(|(_, occupied)| **occupied).map(move |(j, _)| (i, j))
+            }).collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn tm_occupies(&self, corner: (usize, usize), pos: (usize, usize)) -> bool {
+        match self {
+            GridEntity::TimeMachine { mask, .. } if pos.0 >= corner.0 && pos.1 >= corner.1 => {
+                mask
+                    .get(pos.0 - corner.0)
+                    .and_then(|column| column.get(pos.1 - corner.1))
+                    .copied()
+                    .unwrap_or(false)
+            },
+            _ => false
+        }
+    }
+
+    fn is_floor(&self) -> bool {
+        match self {
+            GridEntity::Custom(custom) => custom.0.is_floor(),
+            _ => matches!(self, GridEntity::Plate { .. } | GridEntity::Door { .. } | GridEntity::Key { .. } | GridEntity::Lock { .. })
+        }
+    }
+
+    pub fn inventory(&self) -> Option<&Inventory> {
+        match self {
+            GridEntity::Player { inventory, .. } |
+            GridEntity::PastPlayer { inventory, .. } => Some(inventory),
+            _ => None
+        }
+    }
+
+    fn inventory_mut(&mut self) -> Option<&mut Inventory> {
+        match self {
+            GridEntity::Player { inventory, .. } |
+            GridEntity::PastPlayer { inventory, .. } => Some(inventory),
+            _ => None
+        }
+    }
+
     fn is_pushable(&self) -> bool {
         match self {
-            GridEntity::TimeMachine { .. } => false,
-            GridEntity::None { .. } => false,
+            GridEntity::TimeMachine { pushable, .. } => *pushable,
+            GridEntity::Custom(custom) => custom.0.is_pushable(),
+            GridEntity::None => false,
             _ => true
         }
     }
@@ -102,26 +178,36 @@ impl GridEntity {
         }
     }
 
-    pub fn try_add_part_to_grid(&mut self, x: usize, y: usize, opt_part_type: Option<&TimeMachinePartType>) {
-        match (self, opt_part_type) {
-            (
-                GridEntity::TimeMachine { grid, .. }, 
-                Some(part_type)
-            ) => {
-                while grid.len() <= x {
-                    grid.push(Vec::new());
-                }
-    
-                while grid[ x ].len() <= y {
-                    grid[ x ].push((TimeMachinePartType::Middle, 0));
-                }
-    
-                grid[ x ][ y ] = (
-                    *part_type,
-                    0
-                );
-            },
-           _ => {}
+    pub fn try_add_part_to_grid(
+        &mut self, 
+        x: usize, 
+        y: usize, 
+        opt_part_type: Option<&TimeMachinePartType>, 
+        opt_pushable: Option<&PushableTimeMachine>,
+        opt_kind: Option<&TimeMachineKind>
+    ) {
+        if let (GridEntity::TimeMachine { grid, mask, pushable, kind, .. }, Some(part_type)) = (self, opt_part_type) {
+            *pushable = *pushable || opt_pushable.is_some_and(|part_pushable| part_pushable.0);
+
+            if let Some(&TimeMachineKind::Stasis { ticks }) = opt_kind {
+                *kind = TimeMachineKind::Stasis { ticks };
+            }
+
+            while grid.len() <= x {
+                grid.push(Vec::new());
+                mask.push(Vec::new());
+            }
+
+            while grid[ x ].len() <= y {
+                grid[ x ].push((TimeMachinePartType::Middle, 0));
+                mask[ x ].push(false);
+            }
+
+            grid[ x ][ y ] = (
+                *part_type,
+                0
+            );
+            mask[ x ][ y ] = true;
         }
     }
 
@@ -139,8 +225,11 @@ impl GridEntity {
                 if grid[ x ][ y ].1 == 0 {
                     grid[ x ][ y ].1 = add_index;
                 } else {
+                    // Goes into the time machine that's inside of this one
+                    let inner_index = grid[ x ][ y ].1;
+
                     GridEntity::add_to_grid(
-                        entity_index,
+                        inner_index,
                         add_index,
                         pos,
                         entities
@@ -151,26 +240,9 @@ impl GridEntity {
         }
     }
 
-    fn get_depth_of(
-        &self, 
-        corner: (usize, usize), 
-        pos: (usize, usize), 
-        index: usize,
-        entities: &Vec<((usize, usize), GridEntity)>
-    ) -> usize {
-        match self {
-            GridEntity::TimeMachine { grid, .. } => {
-                let entity = &entities[ grid[ pos.0 - corner.0 ][ pos.1 - corner.1 ].1 ];
-
-                entity.1.get_depth_of(entity.0, pos, index, entities)
-            },
-            _ => 0
-        }
-    }
-
-    fn get_contents<'a>(
+    fn get_contents(
         &self,
-        entities: &'a Vec<((usize, usize), GridEntity)>
+        entities: &Vec<((usize, usize), GridEntity)>
     ) -> Vec<usize> {
         match self {
             GridEntity::TimeMachine { grid, .. } => {
@@ -195,15 +267,16 @@ impl GridEntity {
     }
 
     fn change_all_indeces(my_index: usize, from_index: usize, to_index: usize, entities: &mut Vec<((usize, usize), GridEntity)>) {
-        let sizes = if let GridEntity::TimeMachine { grid, .. } = &entities[ my_index ].1 {
-            (grid.len(), grid[0].len())
+        // Columns can be different lengths for non-rectangular time machines
+        let column_lengths: Vec<usize> = if let GridEntity::TimeMachine { grid, .. } = &entities[ my_index ].1 {
+            grid.iter().map(|column| column.len()).collect()
         } else {
-            (0, 0)
+            Vec::new()
         };
 
         if let GridEntity::TimeMachine { .. } = entities[ my_index ].1 {
-            for i in 0..sizes.0 {
-                for j in 0..sizes.1 {
+            for (i, &column_length) in column_lengths.iter().enumerate() {
+                for j in 0..column_length {
                     let index = entities[ my_index ].1.get_tm_grid()[i][j].1;
                     
                     if index == from_index {
@@ -221,11 +294,50 @@ impl GridEntity {
         }
     }
 
+    pub fn variant(&self) -> &'static str {
+        match self {
+            GridEntity::Player { .. } => "Player",
+            GridEntity::PastPlayer { .. } => "PastPlayer",
+            GridEntity::Box { .. } => "Box",
+            GridEntity::TimeMachine { .. } => "TimeMachine",
+            GridEntity::Plate { .. } => "Plate",
+            GridEntity::Door { .. } => "Door",
+            GridEntity::Key { .. } => "Key",
+            GridEntity::Lock { .. } => "Lock",
+            GridEntity::Custom(custom) => custom.0.variant(),
+            GridEntity::None => "None"
+        }
+    }
+
+    // There's only ever one player so its id doesn't matter
+    pub fn is_entity(&self, variant: &str, id: usize) -> bool {
+        self.variant() == variant && (matches!(self, GridEntity::Player { .. }) || self.id() == id)
+    }
+
+    pub fn id(&self) -> usize {
+        match self {
+            GridEntity::Custom(custom) => custom.0.id(),
+            GridEntity::PastPlayer { id, .. } |
+            GridEntity::Box { id } |
+            GridEntity::TimeMachine { id, .. } |
+            GridEntity::Plate { id, .. } |
+            GridEntity::Door { id, .. } |
+            GridEntity::Key { id } |
+            GridEntity::Lock { id } => *id,
+            _ => 0
+        }
+    }
+
     fn change_id(&mut self, new_id: usize) {
         match self {
             GridEntity::PastPlayer { id, .. } |
             GridEntity::Box { id } |
-            GridEntity::TimeMachine { id, .. } => *id = new_id,
+            GridEntity::TimeMachine { id, .. } |
+            GridEntity::Plate { id, .. } |
+            GridEntity::Door { id, .. } |
+            GridEntity::Key { id } |
+            GridEntity::Lock { id } => *id = new_id,
+            GridEntity::Custom(custom) => custom.0.set_id(new_id),
             _ => {}
         }
     }
@@ -257,6 +369,32 @@ impl GridEntity {
         bevy_level_entity: Entity
     ) {
         match self {
+            // The editor can remove the player and put it back
+            GridEntity::Player { .. } => {
+                commands.entity(bevy_level_entity).with_children(|parent| {
+                    parent.spawn(PlayerBundle {
+                        component: Player,
+                        position: GridCoords::new(corner.0 as i32, corner.1 as i32),
+                        grid_entity: GridEntityInfo {
+                            variant: "Player",
+                            ..Default::default()
+                        },
+                        sprite_bundle: SpriteSheetBundle { 
+                            sprite: TextureAtlasSprite {
+                                index: 48,
+                                ..Default::default()
+                            },
+                            texture_atlas: tileset_atlas(text_atlases, asset_server),
+                            transform: Transform::from_xyz(
+                                (TILE_SIZE * corner.0 + TILE_SIZE / 2) as f32,
+                                (TILE_SIZE * corner.1 + TILE_SIZE / 2) as f32, 
+                                0.0
+                            ),
+                            ..Default::default() 
+                        },
+                    });
+                });
+            },
             GridEntity::PastPlayer { id, .. } => {
                 commands.entity(bevy_level_entity).with_children(|parent| {
                     parent.spawn(PastPlayerBundle {
@@ -272,14 +410,7 @@ impl GridEntity {
                                 index: 50,
                                 ..Default::default()
                             },
-                            texture_atlas: text_atlases.add(TextureAtlas::from_grid(
-                                asset_server.load("tileset_alt.png").into(), 
-                                Vec2::new(256.0, 256.0), 
-                                8, 
-                                8, 
-                                None, 
-                                None
-                            )),
+                            texture_atlas: tileset_atlas(text_atlases, asset_server),
                             transform: Transform::from_xyz(
                                 (TILE_SIZE * corner.0 + TILE_SIZE / 2) as f32,
                                 (TILE_SIZE * corner.1 + TILE_SIZE / 2) as f32, 
@@ -305,14 +436,7 @@ impl GridEntity {
                                 index: 49,
                                 ..Default::default()
                             },
-                            texture_atlas: text_atlases.add(TextureAtlas::from_grid(
-                                asset_server.load("tileset_alt.png").into(), 
-                                Vec2::new(256.0, 256.0), 
-                                8, 
-                                8, 
-                                None, 
-                                None
-                            )),
+                            texture_atlas: tileset_atlas(text_atlases, asset_server),
                             transform: Transform::from_xyz(
                                 (TILE_SIZE * corner.0 + TILE_SIZE / 2) as f32,
                                 (TILE_SIZE * corner.1 + TILE_SIZE / 2) as f32, 
@@ -323,46 +447,170 @@ impl GridEntity {
                     });
                 });
             },
-            GridEntity::TimeMachine { id, grid, .. } => {
-                for i in 0..grid.len() {
-                    for j in 0..grid[i].len() {
-                        commands.entity(bevy_level_entity).with_children(|parent| {
-                            parent.spawn(TimeMachinePartBundle {
-                                component: TimeMachine,
-                                position: GridCoords::new((corner.0 + i)as i32, (corner.1 + j) as i32),
-                                part_type: grid[i][j].0,
-                                grid_entity: GridEntityInfo {
-                                    variant: "TimeMachine",
-                                    id: *id,
-                                    pos: (i, j),
+            GridEntity::TimeMachine { id, grid, pushable, kind, .. } => {
+                for (i, j) in self.tm_cells() {
+                    commands.entity(bevy_level_entity).with_children(|parent| {
+                        parent.spawn(TimeMachinePartBundle {
+                            component: TimeMachine,
+                            position: GridCoords::new((corner.0 + i)as i32, (corner.1 + j) as i32),
+                            part_type: grid[i][j].0,
+                            pushable: PushableTimeMachine(*pushable),
+                            kind: *kind,
+                            cell: TimeMachineCell::default(),
+                            iid: LdtkIid::default(),
+                            grid_entity: GridEntityInfo {
+                                variant: "TimeMachine",
+                                id: *id,
+                                pos: (i, j),
+                                ..Default::default()
+                            },
+                            sprite_bundle: SpriteSheetBundle { 
+                                sprite: TextureAtlasSprite {
+                                    index: grid[i][j].0.tile_index(),
                                     ..Default::default()
                                 },
-                                sprite_bundle: SpriteSheetBundle { 
-                                    sprite: TextureAtlasSprite {
-                                        index: 36,
-                                        ..Default::default()
-                                    },
-                                    texture_atlas: text_atlases.add(TextureAtlas::from_grid(
-                                        asset_server.load("tileset_alt.png").into(), 
-                                        Vec2::new(256.0, 256.0), 
-                                        8, 
-                                        8, 
-                                        None, 
-                                        None
-                                    )),
-                                    transform: Transform::from_xyz(
-                                        (TILE_SIZE * (corner.0 + i) + TILE_SIZE / 2) as f32,
-                                        (TILE_SIZE * (corner.1 + j) + TILE_SIZE / 2) as f32, 
-                                        0.0
-                                    ),
-                                    ..Default::default() 
-                                },
-                            });
+                                texture_atlas: tileset_atlas(text_atlases, asset_server),
+                                transform: Transform::from_xyz(
+                                    (TILE_SIZE * (corner.0 + i) + TILE_SIZE / 2) as f32,
+                                    (TILE_SIZE * (corner.1 + j) + TILE_SIZE / 2) as f32, 
+                                    0.0
+                                ),
+                                ..Default::default() 
+                            },
                         });
-                    }
+                    });
                 }
             },
-            _ => panic!("Shouldn't be trying to spawn a player or none")
+            GridEntity::Plate { id, .. } => {
+                commands.entity(bevy_level_entity).with_children(|parent| {
+                    parent.spawn(PlateBundle {
+                        component: Plate,
+                        position: GridCoords::new(corner.0 as i32, corner.1 as i32),
+                        grid_entity: GridEntityInfo {
+                            variant: "Plate",
+                            id: *id,
+                            ..Default::default()
+                        },
+                        iid: LdtkIid::default(),
+                        sprite_bundle: SpriteSheetBundle { 
+                            sprite: TextureAtlasSprite {
+                                index: 15,
+                                ..Default::default()
+                            },
+                            texture_atlas: tileset_atlas(text_atlases, asset_server),
+                            transform: Transform::from_xyz(
+                                (TILE_SIZE * corner.0 + TILE_SIZE / 2) as f32,
+                                (TILE_SIZE * corner.1 + TILE_SIZE / 2) as f32, 
+                                0.0
+                            ),
+                            ..Default::default() 
+                        },
+                    });
+                });
+            },
+            GridEntity::Door { id, .. } => {
+                commands.entity(bevy_level_entity).with_children(|parent| {
+                    parent.spawn(DoorBundle {
+                        component: Door,
+                        position: GridCoords::new(corner.0 as i32, corner.1 as i32),
+                        grid_entity: GridEntityInfo {
+                            variant: "Door",
+                            id: *id,
+                            ..Default::default()
+                        },
+                        plates: DoorPlates::default(),
+                        sprite_bundle: SpriteSheetBundle { 
+                            sprite: TextureAtlasSprite {
+                                index: 51,
+                                ..Default::default()
+                            },
+                            texture_atlas: tileset_atlas(text_atlases, asset_server),
+                            transform: Transform::from_xyz(
+                                (TILE_SIZE * corner.0 + TILE_SIZE / 2) as f32,
+                                (TILE_SIZE * corner.1 + TILE_SIZE / 2) as f32, 
+                                0.0
+                            ),
+                            ..Default::default() 
+                        },
+                    });
+                });
+            },
+            GridEntity::Key { id, .. } => {
+                commands.entity(bevy_level_entity).with_children(|parent| {
+                    parent.spawn(KeyBundle {
+                        component: Key,
+                        position: GridCoords::new(corner.0 as i32, corner.1 as i32),
+                        grid_entity: GridEntityInfo {
+                            variant: "Key",
+                            id: *id,
+                            ..Default::default()
+                        },
+                        sprite_bundle: SpriteSheetBundle { 
+                            sprite: TextureAtlasSprite {
+                                index: 52,
+                                ..Default::default()
+                            },
+                            texture_atlas: tileset_atlas(text_atlases, asset_server),
+                            transform: Transform::from_xyz(
+                                (TILE_SIZE * corner.0 + TILE_SIZE / 2) as f32,
+                                (TILE_SIZE * corner.1 + TILE_SIZE / 2) as f32, 
+                                0.0
+                            ),
+                            ..Default::default() 
+                        },
+                    });
+                });
+            },
+            GridEntity::Lock { id, .. } => {
+                commands.entity(bevy_level_entity).with_children(|parent| {
+                    parent.spawn(LockBundle {
+                        component: Lock,
+                        position: GridCoords::new(corner.0 as i32, corner.1 as i32),
+                        grid_entity: GridEntityInfo {
+                            variant: "Lock",
+                            id: *id,
+                            ..Default::default()
+                        },
+                        sprite_bundle: SpriteSheetBundle { 
+                            sprite: TextureAtlasSprite {
+                                index: 31,
+                                ..Default::default()
+                            },
+                            texture_atlas: tileset_atlas(text_atlases, asset_server),
+                            transform: Transform::from_xyz(
+                                (TILE_SIZE * corner.0 + TILE_SIZE / 2) as f32,
+                                (TILE_SIZE * corner.1 + TILE_SIZE / 2) as f32, 
+                                0.0
+                            ),
+                            ..Default::default() 
+                        },
+                    });
+                });
+            },
+            GridEntity::Custom(custom) => {
+                commands.entity(bevy_level_entity).with_children(|parent| {
+                    parent.spawn(CustomEntityBundle::new(custom.clone(), corner, text_atlases, asset_server));
+                });
+            },
+            _ => panic!("Shouldn't be trying to spawn none")
+        }
+    }
+}
+
+// Why the editor can't put a time machine somewhere, the grid is left as it was
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlacementRefused {
+    // Parts of bigger machines have walls on two sides at most and the single part ones have
+    // an opening, so there are no parts for closed machines thinner than 2 by 2
+    TooThin,
+    Overlapping
+}
+
+impl std::fmt::Display for PlacementRefused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooThin => write!(f, "Time machines have to be at least 2 by 2"),
+            Self::Overlapping => write!(f, "Time machines can't overlap walls or other time machines")
         }
     }
 }
@@ -370,32 +618,65 @@ impl GridEntity {
 #[derive(Resource, Debug, PartialEq, Clone)]
 pub struct Grid {
     entities: Vec<((usize, usize), GridEntity)>,
-    entity_grid: Vec<Vec<usize>>
+    entity_grid: Vec<Vec<usize>>,
+    // Cells from the Collisions IntGrid layer, nothing can ever be in them
+    walls: Vec<Vec<bool>>,
+    // Indeces of the floor entities in each cell, they never move so this only ever grows.
+    // Removed ones stay in it as GridEntity::None
+    floor_grid: Vec<Vec<Vec<usize>>>,
+    // ((variant, id) of the clone, (variant, id) of the original) for every entity that
+    // time travel duplicated, indeces aren't kept since copying a grid back can change them
+    lineage: Vec<((&'static str, usize), (&'static str, usize))>
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Grid {
     pub fn new() -> Self {
         Self {
             entities: vec![((0, 0), GridEntity::None)],
-            entity_grid: Vec::new()
+            entity_grid: Vec::new(),
+            walls: Vec::new(),
+            floor_grid: Vec::new(),
+            lineage: Vec::new()
         }
     }
 
     pub fn new_sized(width: usize, height: usize) -> Self {
         Self {
             entities: vec![((0, 0), GridEntity::None)],
-            entity_grid: vec![vec![0; height]; width]
+            entity_grid: vec![vec![0; height]; width],
+            walls: vec![vec![false; height]; width],
+            floor_grid: vec![vec![Vec::new(); height]; width],
+            lineage: Vec::new()
         }
     }
 
+    pub fn set_wall(&mut self, x: usize, y: usize, is_wall: bool) {
+        self.walls[ x ][ y ] = is_wall;
+    }
+
+    pub fn is_wall(&self, x: usize, y: usize) -> bool {
+        self.walls[ x ][ y ]
+    }
+
     pub fn height(&self) -> usize {
-        self.entity_grid[0].len()
+        self.entity_grid.first().map_or(0, |column| column.len())
     }
 
     pub fn width(&self) -> usize {
         self.entity_grid.len()
     }
 
+    // What entity_grid holds at (x, y), a time machine covers whatever is inside of it
+    pub fn grid_index(&self, x: usize, y: usize) -> usize {
+        self.entity_grid[ x ][ y ]
+    }
+
     pub fn num_entities(&self) -> usize {
         self.entities.len()
     }
@@ -406,49 +687,25 @@ impl Grid {
 
     pub fn get_entity<'a>(&'a self, grid_entity_info: &GridEntityInfo) -> Option<&'a ((usize, usize), GridEntity)> {
         self.entities.iter().find(|(_, grid_entity)| {
-            match (grid_entity, grid_entity_info.variant) {
-                (GridEntity::Player { .. }, "Player") => true,
-                (GridEntity::PastPlayer { id: entity_id, .. }, "PastPlayer") |
-                (GridEntity::Box {id: entity_id, .. }, "Box") |
-                (GridEntity::TimeMachine { id: entity_id, .. }, "TimeMachine") => *entity_id == grid_entity_info.id,
-                _ => false
-            }
+            grid_entity.is_entity(grid_entity_info.variant, grid_entity_info.id)
         })
     }
 
     pub fn get_entity_mut<'a>(&'a mut self, grid_entity_info: &GridEntityInfo) -> Option<&'a mut ((usize, usize), GridEntity)> {
         self.entities.iter_mut().find(|(_, grid_entity)| {
-            match (grid_entity, grid_entity_info.variant) {
-                (GridEntity::Player { .. }, "Player") => true,
-                (GridEntity::PastPlayer { id: entity_id, .. }, "PastPlayer") |
-                (GridEntity::Box {id: entity_id, .. }, "Box") |
-                (GridEntity::TimeMachine { id: entity_id, .. }, "TimeMachine") => *entity_id == grid_entity_info.id,
-                _ => false
-            }
+            grid_entity.is_entity(grid_entity_info.variant, grid_entity_info.id)
         })
     }
     
     pub fn get_entity_from_id<'a>(&'a self, variant: &str, id: usize) -> Option<&'a ((usize, usize), GridEntity)> {
         self.entities.iter().find(|(_, grid_entity)| {
-            match (grid_entity, variant) {
-                (GridEntity::Player { .. }, "Player") => true,
-                (GridEntity::PastPlayer { id: entity_id, .. }, "PastPlayer") |
-                (GridEntity::Box {id: entity_id, .. }, "Box") |
-                (GridEntity::TimeMachine { id: entity_id, .. }, "TimeMachine") => *entity_id == id,
-                _ => false
-            }
+            grid_entity.is_entity(variant, id)
         })
     }
 
     pub fn get_entity_from_id_mut<'a>(&'a mut self, variant: &str, id: usize) -> Option<&'a mut ((usize, usize), GridEntity)> {
         self.entities.iter_mut().find(|(_, grid_entity)| {
-            match (grid_entity, variant) {
-                (GridEntity::Player { .. }, "Player") => true,
-                (GridEntity::PastPlayer { id: entity_id, .. }, "PastPlayer") |
-                (GridEntity::Box {id: entity_id, .. }, "Box") |
-                (GridEntity::TimeMachine { id: entity_id, .. }, "TimeMachine") => *entity_id == id,
-                _ => false
-            }
+            grid_entity.is_entity(variant, id)
         })
     }
     
@@ -468,7 +725,7 @@ impl Grid {
         }
     }
     
-    pub fn get_entity_from_pos<'a>(&'a self, x: usize, y: usize) -> Option<&'a ((usize, usize), GridEntity)> {
+    pub fn get_entity_from_pos(&self, x: usize, y: usize) -> Option<&((usize, usize), GridEntity)> {
         if x < self.width() && y < self.height() {
             Some(&self.entities[self.entity_grid[x][y]])
         } else {
@@ -476,7 +733,7 @@ impl Grid {
         }
     }
 
-    pub fn get_entity_from_pos_mut<'a>(&'a mut self, x: usize, y: usize) -> Option<&'a mut ((usize, usize), GridEntity)> {
+    pub fn get_entity_from_pos_mut(&mut self, x: usize, y: usize) -> Option<&mut ((usize, usize), GridEntity)> {
         if x < self.width() && y < self.height() {
             Some(&mut self.entities[self.entity_grid[x][y]])
         } else {
@@ -486,49 +743,25 @@ impl Grid {
 
     pub fn get_entity_index(&self, grid_entity_info: &GridEntityInfo) -> Option<usize> {
         self.entities.iter().position(|(_, grid_entity)| {
-            match (grid_entity, grid_entity_info.variant) {
-                (GridEntity::Player { .. }, "Player") => true,
-                (GridEntity::PastPlayer { id: entity_id, .. }, "PastPlayer") |
-                (GridEntity::Box {id: entity_id, .. }, "Box") |
-                (GridEntity::TimeMachine { id: entity_id, .. }, "TimeMachine") => *entity_id == grid_entity_info.id,
-                _ => false
-            }
+            grid_entity.is_entity(grid_entity_info.variant, grid_entity_info.id)
         })
     }
 
     pub fn get_entity_index_from_id(&self, variant: &str, id: usize) -> Option<usize> {
         self.entities.iter().position(|(_, grid_entity)| {
-            match (grid_entity, variant) {
-                (GridEntity::Player { .. }, "Player") => true,
-                (GridEntity::PastPlayer { id: entity_id, .. }, "PastPlayer") |
-                (GridEntity::Box {id: entity_id, .. }, "Box") |
-                (GridEntity::TimeMachine { id: entity_id, .. }, "TimeMachine") => *entity_id == id,
-                _ => false
-            }
+            grid_entity.is_entity(variant, id)
         })
     }
 
     pub fn get_all_of_type(&self, variant: &str) -> Vec<&((usize, usize), GridEntity)> {
         self.entities.iter().filter(|(_, grid_entity)| {
-            match (grid_entity, variant) {
-                (GridEntity::Player { .. }, "Player") => true,
-                (GridEntity::PastPlayer { .. }, "PastPlayer") |
-                (GridEntity::Box { .. }, "Box") |
-                (GridEntity::TimeMachine { .. }, "TimeMachine") => true,
-                _ => false
-            }
+            grid_entity.variant() == variant
         }).collect()
     }
 
     pub fn get_all_of_type_mut(&mut self, variant: &str) -> Vec<&mut ((usize, usize), GridEntity)> {
         self.entities.iter_mut().filter(|(_, grid_entity)| {
-            match (grid_entity, variant) {
-                (GridEntity::Player { .. }, "Player") => true,
-                (GridEntity::PastPlayer { .. }, "PastPlayer") |
-                (GridEntity::Box { .. }, "Box") |
-                (GridEntity::TimeMachine { .. }, "TimeMachine") => true,
-                _ => false
-            }
+            grid_entity.variant() == variant
         }).collect()
     }
 
@@ -537,16 +770,28 @@ impl Grid {
             let id = grid_entity_info.id;
 
             self.entities.push(((x, y), match grid_entity_info.variant {
-                "Player" => GridEntity::Player { movements: Vec::new() },
-                "PastPlayer" => GridEntity::PastPlayer { id, movements: Vec::new() },
+                "Player" => GridEntity::Player { actions: Vec::new(), inventory: Inventory::default() },
+                "PastPlayer" => GridEntity::PastPlayer { id, actions: Vec::new(), inventory: Inventory::default() },
                 "Box" => GridEntity::Box { id },
                 "TimeMachine" => GridEntity::TimeMachine { 
-                    start_instance: None, 
+                    start_instance: TimeMachineState::Idle, 
                     id, 
-                    grid: Vec::new()
+                    grid: Vec::new(),
+                    mask: Vec::new(),
+                    pushable: false,
+                    kind: TimeMachineKind::Rewind
                 },
+                "Plate" => GridEntity::Plate { id, active: false },
+                "Door" => GridEntity::Door { id, plates: Vec::new(), open: false },
+                "Key" => GridEntity::Key { id },
+                "Lock" => GridEntity::Lock { id },
                 invalid_varient => panic!("Invalid GridEntity varient: {}", invalid_varient)
             }));
+
+            // The corner isn't always a part of the time machine, so it comes from the part's position
+            if grid_entity_info.variant == "TimeMachine" {
+                self.entities.last_mut().unwrap().0 = (x - grid_entity_info.pos.0, y - grid_entity_info.pos.1);
+            }
             
             self.entities.len() - 1
         })
@@ -557,6 +802,29 @@ impl Grid {
     // If variant is invalid it panics
     pub fn add_entity_to_pos(&mut self, x: usize, y: usize, grid_entity_info: &mut GridEntityInfo) {
         let index = self.add_entity(x, y, &*grid_entity_info);
+
+        self.place_index(x, y, index, grid_entity_info);
+    }
+
+    // Custom entities can't be made from their GridEntityInfo, so they come with their behaviour
+    pub fn add_custom_entity_to_pos(&mut self, x: usize, y: usize, custom: CustomEntity, grid_entity_info: &mut GridEntityInfo) {
+        let index = self.get_entity_index(grid_entity_info).unwrap_or_else(|| {
+            self.entities.push(((x, y), GridEntity::Custom(custom)));
+            self.entities.len() - 1
+        });
+
+        self.place_index(x, y, index, grid_entity_info);
+    }
+
+    fn place_index(&mut self, x: usize, y: usize, index: usize, grid_entity_info: &mut GridEntityInfo) {
+        if self.entities[ index ].1.is_floor() {
+            if !self.floor_grid[ x ][ y ].contains(&index) {
+                self.floor_grid[ x ][ y ].push(index);
+            }
+
+            return;
+        }
+
         let current_index = &mut self.entity_grid[x][y];
 
         if *current_index == 0 {
@@ -574,7 +842,7 @@ impl Grid {
             GridEntity::add_to_grid(
                 index, 
                 *current_index, 
-                self.entities[index].0, 
+                (x, y), 
                 &mut self.entities
             );
 
@@ -583,15 +851,31 @@ impl Grid {
     }
 
     // !!Assumes that player is in the time machine!!
-    pub fn replace_time_machine(&mut self, mut new_grid: Grid, time_machine_index: usize, t: usize, end_t: usize) {
-        // println!("\n0.) {}\npast: {}","-".repeat(30) , self);
+    // Returns the (clone index, original index) of everything that got duplicated
+    pub fn replace_time_machine(
+        &mut self, 
+        new_grid: Grid, 
+        time_machine_index: usize, 
+        t: usize, 
+        end_t: usize, 
+        duplication_rule: DuplicationRule
+    ) -> Vec<(usize, usize)> {
+        trace!("replace_time_machine step 0: {}", self);
         
         let player_index = self.get_entity_index_from_id("Player", 0).unwrap();
+        let time_machine_id = self.time_machine_id(time_machine_index);
 
         // replace player with past_player
         self.replace_player_to_pos(
-            if let Some((_, GridEntity::Player { movements })) = &new_grid.get_entity_from_id("Player", 0) {
-                movements[t..].to_vec()
+            if let Some((_, GridEntity::Player { actions, .. })) = &new_grid.get_entity_from_id("Player", 0) {
+                let mut actions = Action::after_tick(actions, t);
+
+                // The click that sent the player back, the time machine departs on its own after that
+                if actions.last() == Some(&Action::Activate(time_machine_id)) {
+                    actions.pop();
+                }
+
+                actions
             } else {
                 panic!("Player index did index player (should be unreachable) index: {}, grid: {}", player_index, new_grid);
             }, 
@@ -601,15 +885,41 @@ impl Grid {
         // Remove the old player reference
         self.entities[ player_index ] = ((0, 0), GridEntity::None);
 
-        // println!("\n1.) {}\npast: {}","-".repeat(30) , self);
+        trace!("replace_time_machine step 1: {}", self);
+
+        self.replace_time_machine_contents(new_grid, time_machine_index, end_t, duplication_rule)
+    }
+
+    // Sends the contents of new_grid's time machine back into this grid's time machine
+    // without the player. Only this grid changes, the present goes on as it is since
+    // what arrived back then has been waiting in the machine ever since
+    pub fn send_time_machine_contents(
+        &mut self, 
+        new_grid: Grid, 
+        time_machine_index: usize, 
+        end_t: usize, 
+        duplication_rule: DuplicationRule
+    ) -> Vec<(usize, usize)> {
+        self.replace_time_machine_contents(new_grid, time_machine_index, end_t, duplication_rule)
+    }
 
+    // Puts the contents of new_grid's time machine into this grid's time machine
+    fn replace_time_machine_contents(
+        &mut self, 
+        mut new_grid: Grid, 
+        time_machine_index: usize, 
+        end_t: usize, 
+        duplication_rule: DuplicationRule
+    ) -> Vec<(usize, usize)> {
         let (old_corner, ref old_tm_entity) = self.entities[ time_machine_index ];
         let old_contents = old_tm_entity.get_contents(&self.entities);
         
         let (new_corner, ref new_tm_entity) = new_grid.entities[ time_machine_index ];
         let new_contents = new_tm_entity.get_contents(&new_grid.entities);
 
-        // println!("\n\nnew_contents: {:?}\nold_contents: {:?}\n\n", new_contents, old_contents);
+        trace!("new_contents: {:?}, old_contents: {:?}", new_contents, old_contents);
+
+        let mut duplicated: Vec<(usize, usize)> = Vec::new();
 
         // Add the stuff that is new in the time machine and
         // Change the stuff that was cloned via the time machine
@@ -620,16 +930,25 @@ impl Grid {
 
                 new_grid.change_all_indeces(index, new_index);
                 self.entities.push(std::mem::take(&mut new_grid.entities[ index ]));
-                // println!("{:?} - {:?} + {:?}", new_grid.entities[ new_index ].0, new_corner, old_corner);
-
-                if pos.0 < new_corner.0 {
-                    println!("index: {}\nnew_index: {}\nentity: {:?}\nnew_corner: {:?}", index, new_index, self.entities.last(), new_corner);
-                }
 
                 self.entities[ new_index ].0 = (
                     (pos.0 - new_corner.0) + old_corner.0,
                     (pos.1 - new_corner.1) + old_corner.1
                 );
+            } else if !old_contents.contains( &index ) && 
+                self.entities[ index ].1 != GridEntity::None && 
+                duplication_rule == DuplicationRule::Move 
+            {
+                // The original leaves the past and arrives in the time machine instead
+                self.take_out(index);
+
+                self.entities[ index ] = (
+                    (
+                        (new_grid.entities[ index ].0.0 - new_corner.0) + old_corner.0,
+                        (new_grid.entities[ index ].0.1 - new_corner.1) + old_corner.1
+                    ),
+                    new_grid.entities[ index ].1.clone()
+                );
             } else if !old_contents.contains( &index ) {
                 let new_index = self.num_entities();
                 let mut new_entity = new_grid.entities[ index ].clone();
@@ -637,21 +956,32 @@ impl Grid {
                 self.entities.push(new_entity.clone());
                 new_grid.entities.push(new_entity);
 
-                // println!("from_index: {}, to_index: {}, grid: {}", index, new_index, self);
+                trace!("from_index: {}, to_index: {}, grid: {}", index, new_index, self);
 
                 new_grid.change_all_indeces(index, new_index);
+
+                // The player was already replaced with a past player, so it isn't a duplicate
+                if self.entities[ index ].1 != GridEntity::None {
+                    duplicated.push((new_index, index));
+                }
             }
         }
 
-        // println!("\n2.) {}\npast: {}","-".repeat(30) , self);
+        for &(clone, original) in duplicated.iter() {
+            let key = |entity: &GridEntity| (entity.variant(), entity.id());
+
+            self.lineage.push((key(&self.entities[ clone ].1), key(&self.entities[ original ].1)));
+        }
+
+        trace!("replace_time_machine step 2: {}", self);
         
         // Delete the stuff that was overided by the new time machine and
         // update the position of the stuff that stayed in the time machine
         for &index in old_contents.iter() {
             if new_contents.contains( &index ) { // Updating posititon
-                // println!("new_grid_entity: {:?}, new_corner: {:?}, old_corner: {:?}", new_grid.entities[ index ].0, new_corner, old_corner);
+                trace!("new_grid_entity: {:?}, new_corner: {:?}, old_corner: {:?}", new_grid.entities[ index ].0, new_corner, old_corner);
                 
-                // println!("{:?} - {:?} + {:?}", new_grid.entities[ index ].0, new_corner, old_corner);
+                trace!("{:?} - {:?} + {:?}", new_grid.entities[ index ].0, new_corner, old_corner);
                 self.entities[ index ].0 = (
                     (new_grid.entities[ index ].0.0 - new_corner.0) + old_corner.0,
                     (new_grid.entities[ index ].0.1 - new_corner.1) + old_corner.1
@@ -661,55 +991,163 @@ impl Grid {
             }
         }
 
-        // println!("\n3.) {}\npast: {}","-".repeat(30) , self);
+        trace!("replace_time_machine step 3: {}", self);
 
         self.entities[ time_machine_index ].1 = new_grid.entities.remove(time_machine_index).1;
 
         // Sets the activation time for the time machine
-        if let (_, GridEntity::TimeMachine { 
-            start_instance: Some((_, Some(length), Some(end), _)), 
-            .. 
-        }) = &mut self.entities[ time_machine_index ] {
-            *end += *length;
-
-        } else if let (_, GridEntity::TimeMachine { 
-            start_instance: Some((_, length, end, _)), 
-            .. 
-        }) = &mut self.entities[ time_machine_index ] {
-            // *start = end_t;
-            *length = Some(end_t - t);
-            *end = Some(2 * end_t - t);
+        if let (_, GridEntity::TimeMachine { start_instance, .. }) = &mut self.entities[ time_machine_index ] {
+            start_instance.depart(end_t);
         }
 
-        // println!("\n4.) {}\npast: {}","-".repeat(30) , self);
+        trace!("replace_time_machine step 4: {}", self);
+
+        duplicated
     }
 
-    pub fn replace_player_to_pos(&mut self, movements: Vec<IVec2>, pos: (usize, usize)) {
-        let player_index = self
-            .get_entity_index_from_id("Player", 0)
-            .expect("Could not find player in grid");
+    // The (variant, id) of the entities this one was cloned from, the closest one first
+    pub fn ancestors_of(&self, mut entity: (&'static str, usize)) -> Vec<(&'static str, usize)> {
+        let mut ancestors = Vec::new();
 
-        // println!("movements: {:?}, player_index: {}", movements, player_index);
+        while let Some(&(_, original)) = self.lineage.iter().find(|(clone, _)| *clone == entity) {
+            ancestors.push(original);
+            entity = original;
+        }
 
-        let past_player = GridEntity::PastPlayer {
-            movements: movements.clone(),
-            id: self.entities.len()
-        };
+        ancestors
+    }
 
-        self.entities.push((
-            self.entities[ player_index ].0,
-            past_player
-        ));
+    pub fn lineage(&self) -> &Vec<((&'static str, usize), (&'static str, usize))> {
+        &self.lineage
+    }
 
-        self.change_all_indeces(
-            player_index, 
-            self.entities.len() - 1
-        );
+    // Takes everything inside the stasis machine out of the grid until it gets released
+    pub fn hold_time_machine_contents(&mut self, time_machine_index: usize, release_tick: Option<usize>) {
+        let (corner, ref time_machine) = self.entities[ time_machine_index ];
+        let mut held: Vec<HeldEntity> = Vec::new();
 
-        self.entities[ player_index ].0 = pos;
-    }
+        for (i, j) in time_machine.tm_cells() {
+            let index = time_machine.get_tm_grid()[ i ][ j ].1;
 
-    pub fn change_all_indeces(&mut self, from_index: usize, to_index: usize) {
+            if index == 0 { continue; }
+
+            if let Some(held_entity) = held.iter_mut().find(|held_entity| held_entity.entities[ 0 ].0 == index) {
+                held_entity.slots.push((i, j));
+            } else {
+                held.push(HeldEntity {
+                    slots: vec![(i, j)],
+                    entities: [vec![index], self.entities[ index ].1.get_contents(&self.entities)]
+                        .concat()
+                        .into_iter()
+                        .map(|index| (index, ((0, 0), GridEntity::None)))
+                        .collect()
+                });
+            }
+        }
+
+        for held_entity in held.iter_mut() {
+            for (index, (pos, entity)) in held_entity.entities.iter_mut() {
+                let (absolute_pos, grid_entity) = std::mem::take(&mut self.entities[ *index ]);
+
+                *pos = (absolute_pos.0 - corner.0, absolute_pos.1 - corner.1);
+                *entity = grid_entity;
+            }
+        }
+
+        if let GridEntity::TimeMachine { grid, start_instance, .. } = &mut self.entities[ time_machine_index ].1 {
+            for &(i, j) in held.iter().flat_map(|held_entity| held_entity.slots.iter()) {
+                grid[ i ][ j ].1 = 0;
+            }
+
+            *start_instance = TimeMachineState::Holding { release_tick, held };
+        }
+    }
+
+    // Puts the held entities back where they were in the machine. Anything whose
+    // cells got taken in the meantime stays held, returns true once nothing is left
+    pub fn release_time_machine_contents(&mut self, time_machine_index: usize) -> bool {
+        let corner = self.entities[ time_machine_index ].0;
+
+        let held = if let GridEntity::TimeMachine { 
+            start_instance: TimeMachineState::Holding { held, .. }, 
+            .. 
+        } = &mut self.entities[ time_machine_index ].1 {
+            std::mem::take(held)
+        } else {
+            return true;
+        };
+
+        let mut still_held: Vec<HeldEntity> = Vec::new();
+
+        for held_entity in held {
+            let blocked = held_entity.slots.iter().any(|&(i, j)| {
+                self.entities[ time_machine_index ].1.get_tm_grid()[ i ][ j ].1 != 0
+            });
+
+            if blocked {
+                still_held.push(held_entity);
+                continue;
+            }
+
+            let index = held_entity.entities[ 0 ].0;
+
+            for (held_index, (pos, entity)) in held_entity.entities {
+                self.entities[ held_index ] = ((pos.0 + corner.0, pos.1 + corner.1), entity);
+            }
+
+            for (i, j) in held_entity.slots {
+                self.entities[ time_machine_index ].1.get_tm_grid_mut()[ i ][ j ].1 = index;
+            }
+        }
+
+        let released = still_held.is_empty();
+
+        if let GridEntity::TimeMachine { start_instance, .. } = &mut self.entities[ time_machine_index ].1 {
+            if released {
+                start_instance.stop();
+            } else if let TimeMachineState::Holding { held, .. } = start_instance {
+                *held = still_held;
+            }
+        }
+
+        released
+    }
+
+    pub fn replace_player_to_pos(&mut self, actions: Vec<Action>, pos: (usize, usize)) {
+        let player_index = self
+            .get_entity_index_from_id("Player", 0)
+            .expect("Could not find player in grid");
+
+        trace!("actions: {:?}, player_index: {}", actions, player_index);
+
+        // The past player has whatever the player had back then, the player that
+        // travelled keeps what they have now since they come from the newer grid
+        let inventory = if let GridEntity::Player { inventory, .. } = &self.entities[ player_index ].1 {
+            inventory.clone()
+        } else {
+            Inventory::default()
+        };
+
+        let past_player = GridEntity::PastPlayer {
+            actions,
+            id: self.entities.len(),
+            inventory
+        };
+
+        self.entities.push((
+            self.entities[ player_index ].0,
+            past_player
+        ));
+
+        self.change_all_indeces(
+            player_index, 
+            self.entities.len() - 1
+        );
+
+        self.entities[ player_index ].0 = pos;
+    }
+
+    pub fn change_all_indeces(&mut self, from_index: usize, to_index: usize) {
         if from_index == to_index { return; }
 
         for i in 0..self.entity_grid.len() {
@@ -728,101 +1166,488 @@ impl Grid {
         }
     }
 
-    pub fn update_events(&mut self, t: usize, clicked: &mut ClickedTimeMachine) {
-        println!("t: {}", t);
+    // Returns the time machine that got activated this tick, if any, together with the index
+    // of the past player that clicked it. Machines that go off on their own have no activator
+    pub fn update_events(&mut self, t: usize) -> Option<(GridEntityInfo, Option<usize>)> {
+        self.update_custom_entities(t);
+
+        let mut activated = None;
 
         for i in 0..self.entities.len() {
             let stuff = match &mut self.entities[ i ].1 {
-                GridEntity::PastPlayer { movements, .. } => {
-                    (Some(movements.clone()), None)
+                GridEntity::PastPlayer { actions, .. } => {
+                    (Some(actions.clone()), None)
                 },
-                GridEntity::TimeMachine { start_instance: Some((_, _, Some(departure_time), _)), .. } => {
-                    (None, Some(*departure_time))
+                GridEntity::TimeMachine { start_instance: TimeMachineState::Looping { next_departure, .. }, .. } => {
+                    (None, Some(*next_departure))
+                },
+                // Stasis machines get activated again to release their contents
+                GridEntity::TimeMachine { start_instance: TimeMachineState::Holding { release_tick: Some(release_tick), .. }, .. } => {
+                    (None, Some(*release_tick))
                 },
                 _ => {(None, None)}
             };
 
-            if let (Some(mut movements), _) = stuff {
-                // println!("past_player: {:?}", movements);
+            if let (Some(mut actions), _) = stuff {
+                // Past players that are out of actions just stand there
+                if actions.is_empty() { continue; }
 
-                if movements.len() == 0 { return; }
+                if let Action::Move(direction) = actions.remove(0) {
+                    // Past players bumping into things is fine, they just stay put
+                    let _ = self.try_move(i, direction);
+                }
 
-                let movement = movements.remove(0);
+                // Activations don't take a tick so they happen along with the action before them
+                while let Some(&Action::Activate(time_machine_id)) = actions.first() {
+                    actions.remove(0);
 
-                if movement.to_array() != [0, 0] {
-                    println!("{}", self.try_move(i, MoveDirection::from_ivec(movement)));
+                    if let Some((_, time_machine)) = self.get_entity_from_id("TimeMachine", time_machine_id) {
+                        activated = Some((GridEntityInfo::from(time_machine), Some(i)));
+                    }
                 }
 
-                if let GridEntity::PastPlayer { movements: old_movements, .. } = &mut self.entities[ i ].1 { 
-                    *old_movements = movements;
-                    // println!("movements now: {:?}", old_movements);
+                if let GridEntity::PastPlayer { actions: old_actions, .. } = &mut self.entities[ i ].1 { 
+                    *old_actions = actions;
                 }
-
-                // println!("{}", self);
             } else if let (_, Some(departure_time)) = stuff {
-                println!("departure_time: {}", departure_time);
                 if t >= departure_time {
-                    clicked.0 = Some(GridEntityInfo::from(&self.entities[ i ].1))
+                    activated = Some((GridEntityInfo::from(&self.entities[ i ].1), None))
+                }
+            }
+        }
+
+        activated
+    }
+
+    // Each one gets taken out while it updates so it can look at the rest of the grid
+    fn update_custom_entities(&mut self, t: usize) {
+        for i in 0..self.entities.len() {
+            if let (pos, GridEntity::Custom(_)) = self.entities[ i ] {
+                let mut entity = std::mem::take(&mut self.entities[ i ].1);
+
+                if let GridEntity::Custom(custom) = &mut entity {
+                    custom.0.update(pos, t, self);
+                }
+
+                self.entities[ i ].1 = entity;
+            }
+        }
+    }
+
+    pub fn link_door(&mut self, door_id: usize, plate_ids: Vec<usize>) {
+        if let Some((_, GridEntity::Door { plates, .. })) = self.get_entity_from_id_mut("Door", door_id) {
+            *plates = plate_ids;
+        }
+    }
+
+    fn floor_at(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, &GridEntity)> {
+        self.floor_grid[ x ][ y ].iter().map(|&index| (index, &self.entities[ index ].1))
+    }
+
+    // id of the door at (x, y) if there is one and it's closed
+    fn closed_door_at(&self, x: usize, y: usize) -> Option<usize> {
+        self.floor_at(x, y).find_map(|(_, entity)| match entity {
+            GridEntity::Door { id, open: false, .. } => Some(*id),
+            _ => None
+        })
+    }
+
+    // Variant and id of the custom floor entity at (x, y) if it's in the way
+    fn blocking_entity_at(&self, x: usize, y: usize) -> Option<(&'static str, usize)> {
+        self.floor_at(x, y).find_map(|(_, entity)| match entity {
+            GridEntity::Custom(custom) if custom.0.is_blocking() => Some((custom.0.variant(), custom.0.id())),
+            _ => None
+        })
+    }
+
+    // Index of the floor entity of that variant at (x, y)
+    fn floor_entity_at(&self, variant: &str, x: usize, y: usize) -> Option<usize> {
+        self.floor_at(x, y).find(|(_, entity)| entity.variant() == variant).map(|(index, _)| index)
+    }
+
+    // Ids of keys that are in more than one place at once, either lying around and
+    // in someone's inventory or in two inventories. This happens when the player
+    // takes a key back in time to before it was picked up
+    pub fn duplicated_keys(&self) -> Vec<usize> {
+        let mut seen: Vec<usize> = Vec::new();
+        let mut duplicated: Vec<usize> = Vec::new();
+
+        for (_, entity) in self.entities.iter() {
+            let keys = match entity {
+                GridEntity::Key { id } => vec![*id],
+                entity => entity.inventory().map_or(Vec::new(), |inventory| inventory.keys.clone())
+            };
+
+            for key in keys {
+                if seen.contains(&key) && !duplicated.contains(&key) {
+                    duplicated.push(key);
+                }
+
+                seen.push(key);
+            }
+        }
+
+        duplicated
+    }
+
+    // For DuplicationRule::Move, the keys that came back in the time machine are the only
+    // ones left, the copies lying around or carried by anyone outside of it disappear
+    pub fn remove_key_copies_outside(&mut self, time_machine_index: usize, keys: &[usize]) {
+        let contents = self.entities[ time_machine_index ].1.get_contents(&self.entities);
+
+        for index in 0..self.entities.len() {
+            if contents.contains(&index) { continue; }
+
+            match &mut self.entities[ index ].1 {
+                GridEntity::Key { id } if keys.contains(id) => self.entities[ index ] = ((0, 0), GridEntity::None),
+                entity => if let Some(inventory) = entity.inventory_mut() {
+                    inventory.keys.retain(|key| !keys.contains(key));
                 }
             }
         }
     }
 
-    pub fn add_movement(&mut self, movement: IVec2) {
-        // println!("add_movement (start): {}", self);
+    // Runs after update_events so that past players standing on plates count this tick.
+    // Plates go by the innermost thing on them, so time machines don't press plates but
+    // what's inside of them does. Doors stay open while anything, time machines included,
+    // covers them so nothing gets stuck inside a wall
+    pub fn update_plates(&mut self) {
+        for i in 0..self.entities.len() {
+            let (x, y) = self.entities[ i ].0;
+
+            if let GridEntity::Plate { .. } = self.entities[ i ].1 {
+                let pressed = match self.entity_at(x, y) {
+                    GridEntity::Custom(custom) => custom.0.presses_plates(),
+                    entity => matches!(entity, GridEntity::Player { .. } | GridEntity::PastPlayer { .. } | GridEntity::Box { .. })
+                };
+
+                if let GridEntity::Plate { active, .. } = &mut self.entities[ i ].1 {
+                    *active = pressed;
+                }
+            }
+        }
 
         for i in 0..self.entities.len() {
-            let movements_opt = match &mut self.entities[ i ].1 {
-                GridEntity::Player { movements, .. } => {
-                    Some(movements)
+            let (x, y) = self.entities[ i ].0;
+
+            let should_open = if let GridEntity::Door { plates, .. } = &self.entities[ i ].1 {
+                let pressed = !plates.is_empty() && plates.iter().all(|plate_id| {
+                    matches!(self.get_entity_from_id("Plate", *plate_id), Some((_, GridEntity::Plate { active: true, .. })))
+                });
+
+                pressed || self.grid_index(x, y) != 0
+            } else {
+                continue;
+            };
+
+            if let GridEntity::Door { open, .. } = &mut self.entities[ i ].1 {
+                *open = should_open;
+            }
+        }
+    }
+
+    // Dry runs update_events on a clone so nothing here changes the real grid,
+    // the present player is assumed to be waiting the whole time
+    pub fn preview_past_players(&self, t: usize, steps: usize) -> Vec<(usize, Vec<(usize, usize)>)> {
+        let mut preview_grid = self.clone();
+        let mut paths: Vec<(usize, Vec<(usize, usize)>)> = self
+            .get_all_of_type("PastPlayer")
+            .into_iter()
+            .filter_map(|(_, past_player)| {
+                if let GridEntity::PastPlayer { id, .. } = past_player { Some((*id, Vec::new())) } else { None }
+            })
+            .collect();
+
+        for step in 1..=steps {
+            preview_grid.update_events(t + step);
+            preview_grid.update_plates();
+
+            for (id, path) in paths.iter_mut() {
+                if let Some((pos, _)) = preview_grid.get_entity_from_id("PastPlayer", *id) {
+                    path.push(*pos);
+                }
+            }
+        }
+
+        paths
+    }
+
+    pub fn add_action(&mut self, action: Action) {
+        trace!("add_action (start): {}", self);
+
+        for i in 0..self.entities.len() {
+            let actions_opt = match &mut self.entities[ i ].1 {
+                GridEntity::Player { actions, .. } => {
+                    Some(actions)
                 },
-                GridEntity::PastPlayer { movements, .. } => {
-                    Some(movements)
+                GridEntity::PastPlayer { actions, .. } => {
+                    Some(actions)
                 },
                 _ => {None}
             };
 
-            if let Some(movements) = movements_opt {
-                movements.push(movement);
+            if let Some(actions) = actions_opt {
+                actions.push(action);
             }
         }
 
-        // println!("add_movement (end): {}", self);
+        trace!("add_action (end): {}", self);
+    }
+
+    // Pushing moves things one at a time, so if something further along turns out
+    // to be blocked everything that already moved goes back to where it was
+    pub fn try_move(&mut self, entity_index: usize, direction: MoveDirection) -> Result<(), MoveBlocked> {
+        self.undo_if_blocked(|grid| grid.move_entity(entity_index, direction))
+    }
+
+    pub fn try_move_time_machine(&mut self, time_machine_index: usize, direction: MoveDirection) -> Result<(), MoveBlocked> {
+        self.undo_if_blocked(|grid| grid.move_time_machine(time_machine_index, direction))
+    }
+
+    fn undo_if_blocked(&mut self, try_move: impl FnOnce(&mut Self) -> Result<(), MoveBlocked>) -> Result<(), MoveBlocked> {
+        let backup = (self.entities.clone(), self.entity_grid.clone());
+        let result = try_move(self);
+
+        if result.is_err() {
+            (self.entities, self.entity_grid) = backup;
+        }
+
+        result
     }
 
-    pub fn try_move(&mut self, entity_index: usize, direction: MoveDirection) -> bool {
+    fn move_entity(&mut self, entity_index: usize, direction: MoveDirection) -> Result<(), MoveBlocked> {
+        if self.entities[ entity_index ].1.is_tm() {
+            return self.move_time_machine(entity_index, direction);
+        }
+
         let (x1, y1) = self.entities[entity_index].0;
 
         let (x2, y2) = match direction.get_changed_pos(&(x1, y1), self.width(), self.height()) {
             Some((x, y)) => (x, y),
-            None => return false
+            None => return Err(MoveBlocked::OutOfBounds)
         };
 
+        if self.is_wall(x2, y2) {
+            return Err(MoveBlocked::Wall);
+        }
+
+        if let Some(id) = self.closed_door_at(x2, y2) {
+            return Err(MoveBlocked::ClosedDoor(id));
+        }
+
+        if let Some((variant, id)) = self.blocking_entity_at(x2, y2) {
+            return Err(MoveBlocked::Blocked(variant, id));
+        }
+
+        let lock = self.floor_entity_at("Lock", x2, y2);
+
+        if let Some(lock_index) = lock {
+            let has_key = self.entities[ entity_index ].1.inventory().is_some_and(|inventory| inventory.has_key());
+
+            if !has_key {
+                return Err(MoveBlocked::Locked(self.entities[ lock_index ].1.id()));
+            }
+        }
+
+        // Walking into the wall of a pushable time machine pushes the whole thing
+        if let Some(time_machine_index) = self.pushed_time_machine_at(entity_index, x2, y2, direction) {
+            self.move_time_machine(time_machine_index, direction)?;
+        }
+
+        self.check_time_machine_walls((x1, y1), (x2, y2), direction)?;
+
         let next_index = self.entity_index_at(x2, y2);
 
-        if self.entity_at(x2, y2) == &GridEntity::None || (
-            self.entity_at(x2, y2).is_pushable() &&
-            self.try_move(next_index, direction)
-        ) {
-            self.set_to_pos(x1, y1, 0);
-            self.set_to_pos(x2, y2, entity_index);
-            self.entities[entity_index].0 = (x2, y2);
+        if self.entity_at(x2, y2) != &GridEntity::None {
+            if !self.entity_at(x2, y2).is_pushable() {
+                return Err(MoveBlocked::NotPushable);
+            }
 
-            true
-        } else {
-            false
+            self.move_entity(next_index, direction)?;
         }
+
+        self.set_to_pos(x1, y1, 0);
+        self.set_to_pos(x2, y2, entity_index);
+        self.entities[entity_index].0 = (x2, y2);
+
+        if let Some(lock_index) = lock {
+            self.entities[ entity_index ].1.inventory_mut().unwrap().take_key();
+            self.entities[ lock_index ] = ((0, 0), GridEntity::None);
+        }
+
+        if let (Some(key_index), true) = (self.floor_entity_at("Key", x2, y2), self.entities[ entity_index ].1.inventory().is_some()) {
+            let key_id = self.entities[ key_index ].1.id();
+
+            self.entities[ entity_index ].1.inventory_mut().unwrap().keys.push(key_id);
+            self.entities[ key_index ] = ((0, 0), GridEntity::None);
+        }
+
+        Ok(())
     }
 
-    pub fn try_move_entity(&mut self, grid_entity_info: &GridEntityInfo, direction: MoveDirection) -> bool {
+    // Every time machine that only one of from and to is in gets crossed, so the
+    // part on that side has to have an opening facing the way things are moving
+    fn check_time_machine_walls(&self, from: (usize, usize), to: (usize, usize), direction: MoveDirection) -> Result<(), MoveBlocked> {
+        let from_machines = self.time_machines_at(from.0, from.1);
+        let to_machines = self.time_machines_at(to.0, to.1);
+
+        for &index in from_machines.iter().filter(|index| !to_machines.contains(index)) {
+            if !self.part_at(index, from).can_exit_towards(direction) {
+                return Err(MoveBlocked::TimeMachineWall(self.time_machine_id(index)));
+            }
+        }
+
+        for &index in to_machines.iter().filter(|index| !from_machines.contains(index)) {
+            if !self.part_at(index, to).can_enter_moving(direction) {
+                return Err(MoveBlocked::TimeMachineWall(self.time_machine_id(index)));
+            }
+        }
+
+        Ok(())
+    }
+
+    // All the time machines covering (x, y), outermost first
+    pub fn time_machines_at(&self, x: usize, y: usize) -> Vec<usize> {
+        let mut time_machines = Vec::new();
+        let mut index = self.entity_grid[ x ][ y ];
+
+        while let (corner, GridEntity::TimeMachine { grid, .. }) = &self.entities[ index ] {
+            time_machines.push(index);
+            index = grid[ x - corner.0 ][ y - corner.1 ].1;
+        }
+
+        time_machines
+    }
+
+    fn part_at(&self, time_machine_index: usize, pos: (usize, usize)) -> TimeMachinePartType {
+        let (corner, entity) = &self.entities[ time_machine_index ];
+
+        entity.get_tm_grid()[ pos.0 - corner.0 ][ pos.1 - corner.1 ].0
+    }
+
+    fn time_machine_id(&self, time_machine_index: usize) -> usize {
+        if let GridEntity::TimeMachine { id, .. } = self.entities[ time_machine_index ].1 { id } else { 0 }
+    }
+
+    // The outermost pushable time machine at (x, y) that doesn't already have the mover
+    // inside of it, as long as the part at (x, y) can't be entered from that direction
+    fn pushed_time_machine_at(&self, mover_index: usize, x: usize, y: usize, direction: MoveDirection) -> Option<usize> {
+        for index in self.time_machines_at(x, y) {
+            if index == mover_index { return None; }
+
+            if self.entities[ index ].1.is_pushable() && !self.entities[ index ].1.contains_index(mover_index, &self.entities) {
+                return if self.part_at(index, (x, y)).can_enter_moving(direction) { None } else { Some(index) };
+            }
+        }
+
+        None
+    }
+
+    // Moves a time machine and all of its contents by one cell as a rigid block
+    // within whatever is holding it (the level or another time machine)
+    fn move_time_machine(&mut self, time_machine_index: usize, direction: MoveDirection) -> Result<(), MoveBlocked> {
+        let (corner, time_machine) = match &self.entities[ time_machine_index ] {
+            (corner, time_machine @ GridEntity::TimeMachine { pushable: true, .. }) => (*corner, time_machine),
+            _ => return Err(MoveBlocked::NotPushable)
+        };
+
+        let old_cells: Vec<(usize, usize)> = time_machine
+            .tm_cells()
+            .into_iter()
+            .map(|(i, j)| (corner.0 + i, corner.1 + j))
+            .collect();
+
+        let container = self.container_of(time_machine_index);
+
+        let mut new_cells: Vec<(usize, usize)> = Vec::new();
+
+        for pos in old_cells.iter() {
+            match direction.get_changed_pos(pos, self.width(), self.height()) {
+                Some((x, y)) if self.is_wall(x, y) => return Err(MoveBlocked::Wall),
+                Some((x, y)) if self.closed_door_at(x, y).is_some() => return Err(MoveBlocked::ClosedDoor(self.closed_door_at(x, y).unwrap())),
+                Some((x, y)) if self.blocking_entity_at(x, y).is_some() => {
+                    let (variant, id) = self.blocking_entity_at(x, y).unwrap();
+
+                    return Err(MoveBlocked::Blocked(variant, id))
+                },
+                Some((x, y)) if self.floor_entity_at("Lock", x, y).is_some() => {
+                    return Err(MoveBlocked::Locked(self.entities[ self.floor_entity_at("Lock", x, y).unwrap() ].1.id()))
+                },
+                Some(new_pos) => new_cells.push(new_pos),
+                None => return Err(MoveBlocked::OutOfBounds)
+            }
+        }
+
+        // Pushing clears the front one cell at a time
+        for &(x, y) in new_cells.iter().filter(|pos| !old_cells.contains(pos)) {
+            match self.get_slot(container, x, y) {
+                None => return Err(MoveBlocked::TimeMachineWall(container.map_or(0, |index| self.time_machine_id(index)))),
+                Some(0) => {},
+                Some(slot) if !self.entities[ slot ].1.is_pushable() => return Err(MoveBlocked::NotPushable),
+                Some(slot) => self.move_entity(slot, direction)?
+            }
+        }
+
+        for &(x, y) in old_cells.iter() {
+            self.set_slot(container, x, y, 0);
+        }
+
+        for &(x, y) in new_cells.iter() {
+            self.set_slot(container, x, y, time_machine_index);
+        }
+
+        let contents = self.entities[ time_machine_index ].1.get_contents(&self.entities);
+
+        for index in [vec![time_machine_index], contents].concat() {
+            let pos = self.entities[ index ].0;
+
+            self.entities[ index ].0 = direction.get_changed_pos(&pos, self.width(), self.height()).unwrap();
+        }
+
+        Ok(())
+    }
+
+    // Index of the time machine whose grid directly holds the entity, None if it's in the level grid
+    fn container_of(&self, entity_index: usize) -> Option<usize> {
+        self.entities.iter().position(|(_, entity)| {
+            if let GridEntity::TimeMachine { grid, .. } = entity {
+                grid.iter().any(|row| row.iter().any(|(_, index)| *index == entity_index))
+            } else {
+                false
+            }
+        })
+    }
+
+    fn get_slot(&self, container: Option<usize>, x: usize, y: usize) -> Option<usize> {
+        match container.map(|index| &self.entities[ index ]) {
+            Some((corner, time_machine @ GridEntity::TimeMachine { grid, .. })) => {
+                // The gaps of non-rectangular time machines are as good as walls
+                if !time_machine.tm_occupies(*corner, (x, y)) { return None; }
+
+                Some(grid[ x - corner.0 ][ y - corner.1 ].1)
+            },
+            _ => self.entity_grid.get(x)?.get(y).copied()
+        }
+    }
+
+    fn set_slot(&mut self, container: Option<usize>, x: usize, y: usize, entity_index: usize) {
+        match container.map(|index| &mut self.entities[ index ]) {
+            Some((corner, GridEntity::TimeMachine { grid, .. })) => {
+                grid[ x - corner.0 ][ y - corner.1 ].1 = entity_index;
+            },
+            _ => self.entity_grid[ x ][ y ] = entity_index
+        }
+    }
+
+    pub fn try_move_entity(&mut self, grid_entity_info: &GridEntityInfo, direction: MoveDirection) -> Result<(), MoveBlocked> {
         if let Some(index) = self.get_entity_index(grid_entity_info) {
             self.try_move(index, direction)
         } else {
-            false
+            Err(MoveBlocked::NotFound)
         }
     }
 
-    fn entity_at<'a>(&'a self, x: usize, y: usize) -> &'a GridEntity {
+    fn entity_at(&self, x: usize, y: usize) -> &GridEntity {
         let entity = &self.entities[self.entity_grid[ x ][ y ]];
 
         entity.1.entity_at(entity.0, (x, y), &self.entities)
@@ -858,17 +1683,25 @@ impl Grid {
         }
     }
 
+    // How many time machines the entity is inside of, looked for from the cell its part is in
     pub fn set_depth_of(&self, grid_entity_info: &mut GridEntityInfo) {
         let index = self.get_entity_index(grid_entity_info).unwrap();
-        let ((x, y), _) = self.entities[ index ];
-        let entity = self.get_entity_from_pos(x, y).unwrap();
-
-        grid_entity_info.time_machine_depth = entity.1.get_depth_of(
-            entity.0,
-            (x, y),
-            index,
-            &self.entities
-        );
+        let corner = self.entities[ index ].0;
+        let (x, y) = (corner.0 + grid_entity_info.pos.0, corner.1 + grid_entity_info.pos.1);
+
+        let mut depth = 0;
+        let mut current = self.entity_grid[ x ][ y ];
+
+        while current != index && current != 0 {
+            match &self.entities[ current ] {
+                (corner, GridEntity::TimeMachine { grid, .. }) => current = grid[ x - corner.0 ][ y - corner.1 ].1,
+                _ => break
+            }
+
+            depth += 1;
+        }
+
+        grid_entity_info.time_machine_depth = depth;
     }
 
     // Assumes that you will remove the places where the indeces are
@@ -885,7 +1718,137 @@ impl Grid {
         }
     }
 
-    pub fn entities_iter(&self) -> core::slice::Iter<((usize, usize), GridEntity)> {
+    // Nothing but floor entities at (x, y), time machines count as long as their cell is empty
+    pub fn is_free(&self, x: usize, y: usize) -> bool {
+        !self.is_wall(x, y) && self.entity_at(x, y) == &GridEntity::None
+    }
+
+    // Removes the innermost thing at (x, y), a time machine leaves its contents
+    // behind in whatever was holding it. Returns false if there was nothing to remove
+    pub fn remove_at(&mut self, x: usize, y: usize) -> bool {
+        let index = match self.entity_index_at(x, y) {
+            0 => if let Some(&index) = self.time_machines_at(x, y).last() { index } else { return false; },
+            index => index
+        };
+
+        self.take_out(index);
+        self.entities[ index ] = ((0, 0), GridEntity::None);
+
+        true
+    }
+
+    // Takes the entity out of the time machine or level grid holding it, a time machine
+    // leaves its contents behind. The entity itself stays in entities
+    fn take_out(&mut self, index: usize) {
+        let container = self.container_of(index);
+        let corner = self.entities[ index ].0;
+
+        let cells: Vec<((usize, usize), usize)> = match &self.entities[ index ].1 {
+            time_machine @ GridEntity::TimeMachine { grid, .. } => time_machine
+                .tm_cells()
+                .into_iter()
+                .map(|(i, j)| ((corner.0 + i, corner.1 + j), grid[ i ][ j ].1))
+                .collect(),
+            entity if entity.is_floor() => Vec::new(),
+            _ => vec![(corner, 0)]
+        };
+
+        for ((x, y), contents) in cells {
+            // Held things, like the contents of a stasis machine, aren't in any slot
+            if self.get_slot(container, x, y) == Some(index) {
+                self.set_slot(container, x, y, contents);
+            }
+        }
+    }
+
+    // Whether add_time_machine can put a width by height time machine at corner
+    pub fn can_place_time_machine(&self, corner: (usize, usize), width: usize, height: usize) -> Result<(), PlacementRefused> {
+        if width < 2 || height < 2 {
+            return Err(PlacementRefused::TooThin);
+        }
+
+        let overlapping = (0..width).any(|i| (0..height).any(|j| {
+            let (x, y) = (corner.0 + i, corner.1 + j);
+
+            x >= self.width() || y >= self.height() || self.is_wall(x, y) || !self.time_machines_at(x, y).is_empty()
+        }));
+
+        if overlapping { Err(PlacementRefused::Overlapping) } else { Ok(()) }
+    }
+
+    // A closed width by height time machine with its bottom left at corner, anything
+    // already there ends up inside of it. The cells shouldn't have time machines or walls
+    pub fn add_time_machine(&mut self, corner: (usize, usize), width: usize, height: usize, id: usize) {
+        let mut grid_entity_info = GridEntityInfo { variant: "TimeMachine", id, ..Default::default() };
+        let index = self.add_entity(corner.0, corner.1, &grid_entity_info);
+
+        let edge = |inside: bool| if inside { Edge::Inside } else { Edge::Wall };
+
+        for i in 0..width {
+            for j in 0..height {
+                let part_type = TimeMachinePartType::from_edges(TimeMachineEdges::new(
+                    edge(j + 1 < height),
+                    edge(j > 0),
+                    edge(i > 0),
+                    edge(i + 1 < width)
+                ));
+
+                self.entities[ index ].1.try_add_part_to_grid(i, j, Some(&part_type), None, None);
+            }
+        }
+
+        for i in 0..width {
+            for j in 0..height {
+                grid_entity_info.pos = (i, j);
+                self.add_entity_to_pos(corner.0 + i, corner.1 + j, &mut grid_entity_info);
+            }
+        }
+    }
+
+    // Opens the first outside side of the innermost time machine part at (x, y),
+    // or closes it again if it's already open. Returns false if there's no part there
+    pub fn toggle_opening(&mut self, x: usize, y: usize) -> bool {
+        let time_machine_index = if let Some(&index) = self.time_machines_at(x, y).last() { index } else { return false; };
+        let mut edges = self.part_at(time_machine_index, (x, y)).edges();
+
+        let outside: Vec<MoveDirection> = MoveDirection::ALL
+            .into_iter()
+            .filter(|side| edges.get(*side) != Edge::Inside)
+            .collect();
+
+        if outside.iter().any(|side| edges.get(*side) == Edge::Open) {
+            for side in outside {
+                edges.set(side, Edge::Wall);
+            }
+        } else if let Some(&side) = outside.first() {
+            edges.set(side, Edge::Open);
+        } else {
+            return false;
+        }
+
+        let (corner, entity) = &mut self.entities[ time_machine_index ];
+        let corner = *corner;
+
+        entity.get_tm_grid_mut()[ x - corner.0 ][ y - corner.1 ].0 = TimeMachinePartType::from_edges(edges);
+
+        true
+    }
+
+    // (index, old corner, new corner) of everything that isn't where it was in before,
+    // only makes sense if nothing got added or removed in between
+    pub fn moved_since(&self, before: &Grid) -> Vec<(usize, (usize, usize), (usize, usize))> {
+        self.entities
+            .iter()
+            .zip(before.entities.iter())
+            .enumerate()
+            .filter(|(_, ((corner, grid_entity), (old_corner, old_grid_entity)))| {
+                grid_entity != &GridEntity::None && old_grid_entity != &GridEntity::None && corner != old_corner
+            })
+            .map(|(index, ((corner, _), (old_corner, _)))| (index, *old_corner, *corner))
+            .collect()
+    }
+
+    pub fn entities_iter(&self) -> core::slice::Iter<'_, ((usize, usize), GridEntity)> {
         self.entities.iter()
     }
 }
@@ -893,30 +1856,31 @@ impl Grid {
 impl std::fmt::Display for GridEntity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Player { movements } => {
-                if true || movements.len() == 0 {
-                    write!(f, "Player: [..]")
-                } else {
-                    write!(f, "Player: [{}\n        ]", 
-                        movements.iter().fold(String::new(), |string, movement| format!("{}\n            {},", string, movement))
-                    )
-                }
-            }, Self::PastPlayer { movements, id } => {
-                if true || movements.len() == 0 {
-                    write!(f, "PastPlayer[{}]: [..]", id)
-                } else {
-                    write!(f, "PastPlayer[{}]: [{}\n        ]", id,
-                        movements.iter().fold(String::new(), |string, movement| format!("{}\n            {},", string, movement))
-                    )
-                }
+            // The actions are left out, they make the grid too long to read
+            Self::Player { .. } => {
+                write!(f, "Player: [..]")
+            }, Self::PastPlayer { id, .. } => {
+                write!(f, "PastPlayer[{}]: [..]", id)
             }, Self::Box { id } => {
                 write!(f, "Box[ {} ]", id)
-            }, Self::TimeMachine { id, grid, .. } => {
+            }, Self::TimeMachine { id, grid, mask, .. } => {
                 write!(f, "TimeMachine[ {} ]: [{}\n        ]", id,
-                    grid.iter().fold(String::new(), |string, row| {format!("{}\n            [{}  ],", string, 
-                        row.iter().fold(String::new(), |string, (_part, num)| {format!("{} {:2}", string, num)})
+                    grid.iter().zip(mask).fold(String::new(), |string, (row, row_mask)| {format!("{}\n            [{}  ],", string, 
+                        row.iter().zip(row_mask).fold(String::new(), |string, ((_part, num), occupied)| {
+                            if *occupied { format!("{} {:2}", string, num) } else { format!("{}  .", string) }
+                        })
                     )})
                 )
+            }, Self::Plate { id, active } => {
+                write!(f, "Plate[ {} ]: {}", id, if *active { "active" } else { "inactive" })
+            }, Self::Key { id } => {
+                write!(f, "Key[ {} ]", id)
+            }, Self::Lock { id } => {
+                write!(f, "Lock[ {} ]", id)
+            }, Self::Door { id, plates, open } => {
+                write!(f, "Door[ {} ]: {} {:?}", id, if *open { "open" } else { "closed" }, plates)
+            }, Self::Custom(custom) => {
+                write!(f, "{}[ {} ]: {}", custom.0.variant(), custom.0.id(), custom.0.serialize())
             }, Self::None => {
                 write!(f, "None")
             }
@@ -932,9 +1896,337 @@ impl std::fmt::Display for Grid {
                 Grid: [{}\n    ]\n\
             }}", 
             self.entities.iter().enumerate().fold(String::new(), |string, (i, (pos, entity))| format!("{}\n        [{}] @ {:?} -> {},", string, i, pos, entity)), 
-            self.entity_grid.iter().fold(String::new(), |string, row| {
-                format!("{}\n        [{}  ],", string, row.iter().fold(String::new(), |string, num| format!("{} {:2}", string, num)))
+            self.entity_grid.iter().zip(self.walls.iter()).fold(String::new(), |string, (row, wall_row)| {
+                format!("{}\n        [{}  ],", string, row.iter().zip(wall_row.iter()).fold(String::new(), |string, (num, is_wall)| {
+                    if *is_wall { format!("{} ##", string) } else { format!("{} {:2}", string, num) }
+                }))
             })
         )
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player() -> GridEntityInfo {
+        GridEntityInfo { variant: "Player", ..default() }
+    }
+
+    fn add(grid: &mut Grid, variant: &'static str, x: usize, y: usize) -> usize {
+        let id = if variant == "Player" { 0 } else { grid.num_entities() };
+
+        grid.add_entity_to_pos(x, y, &mut GridEntityInfo { variant, id, ..default() });
+
+        id
+    }
+
+    fn make_pushable(grid: &mut Grid, id: usize) {
+        if let Some((_, GridEntity::TimeMachine { pushable, .. })) = grid.get_entity_from_id_mut("TimeMachine", id) {
+            *pushable = true;
+        }
+    }
+
+    #[test]
+    fn blocked_push_leaves_the_grid_as_it_was() {
+        let mut grid = Grid::new_sized(5, 1);
+        add(&mut grid, "Player", 0, 0);
+
+        // The pushed machine moves over before the closed outer one turns out to be in the way
+        grid.add_time_machine((1, 0), 3, 1, 1);
+        grid.add_time_machine((1, 0), 1, 1, 2);
+        make_pushable(&mut grid, 2);
+
+        let before = grid.clone();
+
+        assert_eq!(grid.try_move_entity(&player(), MoveDirection::Right), Err(MoveBlocked::TimeMachineWall(1)));
+        assert_eq!(grid, before);
+    }
+
+    #[test]
+    fn gaps_in_a_time_machine_are_walls_to_what_is_inside() {
+        let mut grid = Grid::new_sized(2, 4);
+
+        // An L with the gap at (1, 1)
+        let mut outer = GridEntityInfo { variant: "TimeMachine", id: 1, ..default() };
+        grid.add_entity(0, 1, &outer);
+
+        for (i, j) in [(0, 0), (0, 1), (1, 1)] {
+            grid.get_entity_mut(&outer).unwrap().1.try_add_part_to_grid(i, j, Some(&TimeMachinePartType::Middle), None, None);
+            outer.pos = (i, j);
+            grid.add_entity_to_pos(i, j + 1, &mut outer);
+        }
+
+        grid.add_time_machine((1, 2), 1, 1, 2);
+        make_pushable(&mut grid, 2);
+
+        let inner_index = grid.get_entity_index_from_id("TimeMachine", 2).unwrap();
+
+        assert_eq!(grid.try_move_time_machine(inner_index, MoveDirection::Down), Err(MoveBlocked::TimeMachineWall(1)));
+        assert_eq!(grid.get_entity_from_id("TimeMachine", 2).unwrap().0, (1, 2));
+    }
+
+    #[test]
+    fn moving_something_missing_is_not_found() {
+        let mut grid = Grid::new_sized(2, 1);
+
+        assert_eq!(grid.try_move_entity(&player(), MoveDirection::Right), Err(MoveBlocked::NotFound));
+    }
+
+    #[test]
+    fn plates_inside_time_machines_get_pressed() {
+        let mut grid = Grid::new_sized(3, 2);
+        let outside = add(&mut grid, "Plate", 0, 0);
+        let inside = add(&mut grid, "Plate", 1, 0);
+        let nested = add(&mut grid, "Plate", 2, 0);
+        let empty = add(&mut grid, "Plate", 2, 1);
+
+        grid.add_time_machine((1, 0), 2, 2, 10);
+        grid.add_time_machine((2, 0), 1, 1, 11);
+        add(&mut grid, "Box", 0, 0);
+        add(&mut grid, "Player", 1, 0);
+        add(&mut grid, "Box", 2, 0);
+
+        grid.update_plates();
+
+        let active = |id| matches!(grid.get_entity_from_id("Plate", id), Some((_, GridEntity::Plate { active: true, .. })));
+
+        assert!(active(outside) && active(inside) && active(nested));
+        assert!(!active(empty));
+    }
+
+    #[test]
+    fn doors_stay_open_under_time_machines() {
+        let mut grid = Grid::new_sized(2, 1);
+        let door = add(&mut grid, "Door", 1, 0);
+        grid.add_time_machine((1, 0), 1, 1, 10);
+
+        grid.update_plates();
+
+        assert!(matches!(grid.get_entity_from_id("Door", door), Some((_, GridEntity::Door { open: true, .. }))));
+        assert_eq!(grid.closed_door_at(1, 0), None);
+    }
+
+    fn give_key(grid: &mut Grid, variant: &str, id: usize, key: usize) {
+        let (_, entity) = grid.get_entity_from_id_mut(variant, id).unwrap();
+        entity.inventory_mut().unwrap().keys.push(key);
+    }
+
+    #[test]
+    fn keys_in_two_places_are_duplicated() {
+        let mut grid = Grid::new_sized(4, 1);
+        let lying = add(&mut grid, "Key", 0, 0);
+        let single = add(&mut grid, "Key", 1, 0);
+        let past_player = add(&mut grid, "PastPlayer", 2, 0);
+        add(&mut grid, "Player", 3, 0);
+
+        assert!(grid.duplicated_keys().is_empty());
+
+        // Lying around and carried
+        give_key(&mut grid, "Player", 0, lying);
+        // Carried by two players
+        give_key(&mut grid, "Player", 0, 20);
+        give_key(&mut grid, "PastPlayer", past_player, 20);
+
+        assert_eq!(grid.duplicated_keys(), vec![lying, 20]);
+        assert!(!grid.duplicated_keys().contains(&single));
+    }
+
+    #[test]
+    fn moved_keys_only_stay_in_the_time_machine() {
+        let mut grid = Grid::new_sized(4, 1);
+        let key = add(&mut grid, "Key", 0, 0);
+        let past_player = add(&mut grid, "PastPlayer", 1, 0);
+        grid.add_time_machine((3, 0), 1, 1, 10);
+        add(&mut grid, "Player", 3, 0);
+
+        give_key(&mut grid, "Player", 0, key);
+        give_key(&mut grid, "PastPlayer", past_player, key);
+
+        let time_machine_index = grid.get_entity_index_from_id("TimeMachine", 10).unwrap();
+        grid.remove_key_copies_outside(time_machine_index, &grid.duplicated_keys());
+
+        assert_eq!(grid.get_entity_from_id("Key", key), None);
+        assert_eq!(grid.get_entity_from_id("PastPlayer", past_player).unwrap().1.inventory().unwrap().keys, Vec::<usize>::new());
+        assert_eq!(grid.get_entity_from_id("Player", 0).unwrap().1.inventory().unwrap().keys, vec![key]);
+        assert!(grid.duplicated_keys().is_empty());
+    }
+
+    // The same box outside of time machine 10 in the past and inside of it now
+    fn box_sent_back() -> (Grid, Grid, usize) {
+        let mut past = Grid::new_sized(3, 1);
+        past.add_time_machine((2, 0), 1, 1, 10);
+        let box_id = add(&mut past, "Box", 0, 0);
+
+        let mut present = Grid::new_sized(3, 1);
+        present.add_time_machine((2, 0), 1, 1, 10);
+        add(&mut present, "Box", 2, 0);
+
+        if let Some((_, GridEntity::TimeMachine { start_instance, .. })) = present.get_entity_from_id_mut("TimeMachine", 10) {
+            start_instance.start_recording(0, past.clone());
+        }
+
+        (past, present, box_id)
+    }
+
+    #[test]
+    fn duplicates_are_kept_track_of_by_id() {
+        let (mut grid, present, box_id) = box_sent_back();
+        let time_machine_index = grid.get_entity_index_from_id("TimeMachine", 10).unwrap();
+
+        let duplicated = grid.replace_time_machine_contents(present, time_machine_index, 5, DuplicationRule::Allow);
+        let clone_id = grid.entities[ duplicated[0].0 ].1.id();
+
+        assert_eq!(grid.get_all_of_type("Box").len(), 2);
+        assert_eq!(grid.lineage(), &vec![(("Box", clone_id), ("Box", box_id))]);
+        assert_eq!(grid.ancestors_of(("Box", clone_id)), vec![("Box", box_id)]);
+    }
+
+    #[test]
+    fn moved_entities_leave_the_past() {
+        let (mut grid, present, box_id) = box_sent_back();
+        let time_machine_index = grid.get_entity_index_from_id("TimeMachine", 10).unwrap();
+
+        let duplicated = grid.replace_time_machine_contents(present, time_machine_index, 5, DuplicationRule::Move);
+
+        assert!(duplicated.is_empty() && grid.lineage().is_empty());
+        assert_eq!(grid.get_all_of_type("Box").len(), 1);
+        assert_eq!(grid.get_entity_from_id("Box", box_id).unwrap().0, (2, 0));
+        assert_eq!(grid.entity_at(2, 0), &GridEntity::Box { id: box_id });
+        assert_eq!(grid.grid_index(0, 0), 0);
+    }
+
+    #[test]
+    fn added_time_machines_close_around_what_is_there() {
+        let mut grid = Grid::new_sized(3, 3);
+        let box_id = add(&mut grid, "Box", 1, 1);
+        grid.add_time_machine((0, 0), 3, 3, 5);
+
+        let time_machine_index = grid.get_entity_index_from_id("TimeMachine", 5).unwrap();
+        let box_index = grid.get_entity_index_from_id("Box", box_id).unwrap();
+
+        assert_eq!(grid.container_of(box_index), Some(time_machine_index));
+        assert_eq!(grid.grid_index(1, 1), time_machine_index);
+
+        // Walls all the way around, the parts only open into each other
+        for side in MoveDirection::ALL {
+            assert_eq!(grid.part_at(time_machine_index, (1, 1)).edges().get(side), Edge::Inside);
+        }
+
+        let corner = grid.part_at(time_machine_index, (0, 0)).edges();
+        assert_eq!(corner, TimeMachineEdges::new(Edge::Inside, Edge::Wall, Edge::Wall, Edge::Inside));
+    }
+
+    #[test]
+    fn free_cells_only_have_floor_in_them() {
+        let mut grid = Grid::new_sized(4, 1);
+        grid.set_wall(0, 0, true);
+        add(&mut grid, "Box", 1, 0);
+        add(&mut grid, "Plate", 2, 0);
+
+        assert_eq!((0..4).map(|x| grid.is_free(x, 0)).collect::<Vec<bool>>(), vec![false, false, true, true]);
+
+        // An empty time machine cell is free, one with something in it isn't
+        grid.add_time_machine((1, 0), 3, 1, 5);
+        assert!(!grid.is_free(1, 0) && grid.is_free(3, 0));
+    }
+
+    #[test]
+    fn removing_takes_the_innermost_thing_first() {
+        let mut grid = Grid::new_sized(3, 1);
+        let box_id = add(&mut grid, "Box", 1, 0);
+        grid.add_time_machine((0, 0), 3, 1, 5);
+
+        assert!(grid.remove_at(1, 0));
+        assert!(grid.get_entity_from_id("Box", box_id).is_none());
+        assert!(grid.get_entity_from_id("TimeMachine", 5).is_some());
+
+        assert!(grid.remove_at(0, 0));
+        assert!(grid.get_entity_from_id("TimeMachine", 5).is_none());
+        assert!(!grid.remove_at(0, 0));
+    }
+
+    #[test]
+    fn removed_time_machines_leave_their_contents() {
+        let mut grid = Grid::new_sized(3, 1);
+        let box_id = add(&mut grid, "Box", 1, 0);
+        grid.add_time_machine((0, 0), 3, 1, 5);
+
+        assert!(grid.remove_at(0, 0));
+
+        let box_index = grid.get_entity_index_from_id("Box", box_id).unwrap();
+        assert_eq!(grid.grid_index(1, 0), box_index);
+        assert_eq!(grid.container_of(box_index), None);
+    }
+
+    #[test]
+    fn openings_toggle_on_the_outside_only() {
+        let mut grid = Grid::new_sized(3, 3);
+        grid.add_time_machine((0, 0), 3, 3, 5);
+        let time_machine_index = grid.get_entity_index_from_id("TimeMachine", 5).unwrap();
+        let left = |grid: &Grid| grid.part_at(time_machine_index, (0, 1)).edges().get(MoveDirection::Left);
+
+        assert!(grid.toggle_opening(0, 1));
+        assert_eq!(left(&grid), Edge::Open);
+
+        assert!(grid.toggle_opening(0, 1));
+        assert_eq!(left(&grid), Edge::Wall);
+
+        // The middle part has no outside to open and empty cells have no part
+        assert!(!grid.toggle_opening(1, 1));
+        grid.remove_at(0, 0);
+        assert!(!grid.toggle_opening(0, 1));
+    }
+
+    #[test]
+    fn depth_counts_the_time_machines_around() {
+        let mut grid = Grid::new_sized(5, 1);
+        grid.add_time_machine((0, 0), 5, 1, 1);
+        grid.add_time_machine((1, 0), 3, 1, 2);
+        let box_id = add(&mut grid, "Box", 2, 0);
+
+        let depth = |variant: &'static str, id: usize, pos: (usize, usize)| {
+            let mut grid_entity_info = GridEntityInfo { variant, id, pos, ..default() };
+            grid.set_depth_of(&mut grid_entity_info);
+
+            grid_entity_info.time_machine_depth
+        };
+
+        assert_eq!(depth("TimeMachine", 1, (0, 0)), 0);
+        assert_eq!(depth("TimeMachine", 2, (2, 0)), 1);
+        assert_eq!(depth("Box", box_id, (0, 0)), 2);
+    }
+
+    #[test]
+    fn finished_past_players_do_not_stop_later_machines() {
+        let mut grid = Grid::new_sized(3, 1);
+        add(&mut grid, "PastPlayer", 0, 0);
+        grid.add_time_machine((2, 0), 1, 1, 5);
+
+        if let Some((_, GridEntity::TimeMachine { start_instance, .. })) = grid.get_entity_from_id_mut("TimeMachine", 5) {
+            *start_instance = TimeMachineState::Holding { release_tick: Some(3), held: Vec::new() };
+        }
+
+        let (released, activator) = grid.update_events(3).expect("The stasis machine didn't release");
+
+        assert_eq!((released.variant, released.id, activator), ("TimeMachine", 5, None));
+    }
+
+    #[test]
+    fn the_editor_only_places_machines_that_have_parts() {
+        let mut grid = Grid::new_sized(4, 3);
+        grid.set_wall(3, 2, true);
+
+        // A single closed cell or a one wide strip has walls on sides no part has walls on
+        assert_eq!(grid.can_place_time_machine((0, 0), 1, 1), Err(PlacementRefused::TooThin));
+        assert_eq!(grid.can_place_time_machine((0, 0), 3, 1), Err(PlacementRefused::TooThin));
+        assert_eq!(grid.can_place_time_machine((0, 0), 1, 2), Err(PlacementRefused::TooThin));
+
+        assert_eq!(grid.can_place_time_machine((0, 0), 2, 2), Ok(()));
+        assert_eq!(grid.can_place_time_machine((2, 1), 2, 2), Err(PlacementRefused::Overlapping));
+
+        grid.add_time_machine((0, 0), 2, 2, 5);
+        assert_eq!(grid.can_place_time_machine((1, 1), 2, 2), Err(PlacementRefused::Overlapping));
+        assert_eq!(grid.can_place_time_machine((2, 0), 2, 2), Ok(()));
+    }
+}
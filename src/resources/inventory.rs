@@ -0,0 +1,15 @@
+// What a Player or PastPlayer is carrying, keys are stored by the id of the Key they were picked up from
+#[derive(Default, Debug, PartialEq, Clone, Hash)]
+pub struct Inventory {
+    pub keys: Vec<usize>
+}
+
+impl Inventory {
+    pub fn has_key(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    pub fn take_key(&mut self) -> Option<usize> {
+        self.keys.pop()
+    }
+}
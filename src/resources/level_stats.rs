@@ -0,0 +1,129 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+const BEST_RESULTS_PATH: &str = "saves/best_results.json";
+
+// What the player has done in the current level. Past players' actions don't count
+#[derive(Resource, Default, Clone, Debug)]
+pub struct LevelStats {
+    pub moves: usize,
+    pub waits: usize,
+    // Every click on a time machine, even ones that get refused
+    pub activations: usize,
+    pub time_travels: usize,
+    pub time: Duration,
+    // Set once the player reaches the goal, nothing gets counted after that
+    pub result: Option<LevelResult>
+}
+
+impl LevelStats {
+    pub fn count_action(&mut self, action: Action) {
+        if self.result.is_some() { return; }
+
+        match action {
+            Action::Move(_) => self.moves += 1,
+            Action::Wait => self.waits += 1,
+            Action::Activate(_) => self.activations += 1
+        }
+    }
+
+    pub fn count_time_travel(&mut self) {
+        if self.result.is_none() { self.time_travels += 1; }
+    }
+
+    pub fn count_time(&mut self, delta: Duration) {
+        if self.result.is_none() { self.time += delta; }
+    }
+
+    pub fn complete(&mut self, level_info: &LevelInfo) -> LevelResult {
+        let result = LevelResult {
+            stars: LevelResult::stars(self.moves + self.waits, self.time_travels, level_info),
+            moves: self.moves,
+            waits: self.waits,
+            time_travels: self.time_travels,
+            seconds: self.time.as_secs_f32()
+        };
+
+        self.result = Some(result);
+        result
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LevelResult {
+    pub stars: usize,
+    pub moves: usize,
+    pub waits: usize,
+    pub time_travels: usize,
+    pub seconds: f32
+}
+
+impl LevelResult {
+    pub const MAX_STARS: usize = 3;
+
+    // One star for finishing and one for each par that's met, levels without a par give its star
+    // for free. Waiting takes a tick like moving does, so waits count towards the move par
+    fn stars(ticks: usize, time_travels: usize, level_info: &LevelInfo) -> usize {
+        1 + level_info.par_moves.is_none_or(|par| ticks <= par) as usize
+            + level_info.par_time_travels.is_none_or(|par| time_travels <= par) as usize
+    }
+
+    // More stars first, then fewer ticks, then fewer time travels, then the faster one
+    pub fn is_better_than(&self, other: &LevelResult) -> bool {
+        let key = |result: &LevelResult| (
+            usize::MAX - result.stars,
+            result.moves + result.waits,
+            result.time_travels
+        );
+
+        match key(self).cmp(&key(other)) {
+            std::cmp::Ordering::Equal => self.seconds < other.seconds,
+            ordering => ordering == std::cmp::Ordering::Less
+        }
+    }
+}
+
+// The best result of every level that's been finished, by LDtk level identifier
+#[derive(Resource, Default)]
+pub struct BestResults(pub HashMap<String, LevelResult>);
+
+impl BestResults {
+    // A missing file just means nothing has been finished yet
+    pub fn load() -> Self {
+        let contents = match std::fs::read_to_string(BEST_RESULTS_PATH) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default()
+        };
+
+        match serde_json::from_str(&contents) {
+            Ok(results) => Self(results),
+            Err(error) => {
+                warn!("Couldn't read the best results, starting over: {}", error);
+                Self::default()
+            }
+        }
+    }
+
+    // Sorted by level identifier so the file doesn't reshuffle on every save
+    pub fn save(&self) -> std::io::Result<()> {
+        let sorted: BTreeMap<&String, &LevelResult> = self.0.iter().collect();
+
+        std::fs::create_dir_all("saves")?;
+        std::fs::write(BEST_RESULTS_PATH, serde_json::to_string_pretty(&sorted)?)
+    }
+
+    // Returns true if the result is the new best for the level
+    pub fn submit(&mut self, identifier: &str, result: LevelResult) -> bool {
+        let is_best = self.0
+            .get(identifier)
+            .is_none_or(|best| result.is_better_than(best));
+
+        if is_best {
+            self.0.insert(identifier.to_string(), result);
+        }
+
+        is_best
+    }
+}
@@ -3,6 +3,24 @@ pub use super::*;
 mod grid;
 pub use grid::*;
 
+mod time_machine_state;
+pub use time_machine_state::*;
+
+mod inventory;
+pub use inventory::*;
+
+mod action;
+pub use action::*;
+
+mod level_stats;
+pub use level_stats::*;
+
+mod solver;
+pub use solver::*;
+
+mod behaviour;
+pub use behaviour::*;
+
 #[derive(Resource)]
 pub struct LevelWindowInfo{
     pub scaling_factor: f32,
@@ -16,4 +34,48 @@ pub struct Ticks(pub usize);
 pub struct Dims {
     pub x: usize,
     pub y: usize
-}
\ No newline at end of file
+}
+
+// What happens to an object that the time machine brings back while it
+// still exists in the past
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DuplicationRule {
+    // Both the original and the copy exist
+    #[default]
+    Allow,
+    // Time travel that would duplicate something gets refused
+    Forbid,
+    // The original disappears from the past, only the one in the time machine is left
+    Move
+}
+
+impl std::str::FromStr for DuplicationRule {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Allow" => Ok(Self::Allow),
+            "Forbid" => Ok(Self::Forbid),
+            "Move" => Ok(Self::Move),
+            _ => Err(())
+        }
+    }
+}
+
+// The current level, from the LDtk level fields
+#[derive(Resource, Default, Clone)]
+pub struct LevelInfo {
+    pub identifier: String,
+    // The Name field, or the LDtk identifier if it isn't set
+    pub name: String,
+    // The cell the player has to reach to finish the level
+    pub goal: Option<(usize, usize)>,
+    // How many moves and time travels a good solution takes
+    pub par_moves: Option<usize>,
+    pub par_time_travels: Option<usize>,
+    pub hint: Option<String>,
+    // Also decides what happens to keys that end up in two places
+    pub duplication_rule: DuplicationRule,
+    // If true, activating a time machine without the player inside sends its contents back on their own
+    pub send_without_player: bool
+}
@@ -0,0 +1,365 @@
+use super::*;
+use std::collections::hash_map::DefaultHasher;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::hash::{Hash, Hasher};
+
+// How deep the search goes for levels without a par
+pub const DEFAULT_SEARCH_TICKS: usize = 30;
+// Grids looked at before giving up, so a hard level can't keep the search going forever
+pub const MAX_SEARCH_NODES: usize = 50_000;
+// How much getting closer to the goal counts compared to using fewer ticks. Above 1 the
+// solutions aren't always the shortest but they get found a lot faster
+pub const GOAL_DISTANCE_WEIGHT: usize = 2;
+// Activations don't take a tick, without a limit the search could click machines forever
+pub const MAX_ACTIVATIONS_PER_TICK: usize = 2;
+
+// Does to the grid what the game does for an action, returns the tick after it or None
+// if it got refused. Moves into walls count as refused since they're the same as waiting
+pub fn simulate_action(grid: &mut Grid, tick: usize, action: Action, level_info: &LevelInfo) -> Option<usize> {
+    if let Action::Activate(id) = action {
+        let time_machine_info = GridEntityInfo::from(&grid.get_entity_from_id("TimeMachine", id)?.1);
+
+        grid.add_action(action);
+        let player_index = grid.get_entity_index_from_id("Player", 0);
+        activate_time_machine(grid, &time_machine_info, player_index, tick, level_info).ok()?;
+        grid.update_plates();
+
+        return Some(tick);
+    }
+
+    let tick = tick + 1;
+
+    if let Action::Move(direction) = action {
+        let player_index = grid.get_entity_index_from_id("Player", 0)?;
+
+        grid.try_move(player_index, direction).ok()?;
+    }
+
+    if let Some((time_machine_info, activator)) = grid.update_events(tick) {
+        // Refused activations of past players don't stop the tick in the game either
+        let _ = activate_time_machine(grid, &time_machine_info, activator, tick, level_info);
+    }
+
+    grid.update_plates();
+    grid.add_action(action);
+
+    Some(tick)
+}
+
+// An action of the solution and where the player was standing when doing it
+pub type SolutionStep = (Action, (usize, usize));
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SearchStatus {
+    Searching,
+    Found(Vec<SolutionStep>),
+    // Nothing reaches the goal within the tick limit
+    NotFound,
+    // Ran out of nodes before it could tell
+    GaveUp
+}
+
+struct SearchNode {
+    parent: usize,
+    step: Option<SolutionStep>,
+    grid: Grid,
+    tick: usize,
+    ticks_used: usize,
+    activations_this_tick: usize
+}
+
+// Best first search from a grid to the goal, favouring grids with the player closer to it.
+// It gets run a few nodes at a time so the game keeps going while it searches
+pub struct HintSearch {
+    nodes: Vec<SearchNode>,
+    // (Reverse(priority), index), the lowest priority comes out first
+    frontier: BinaryHeap<(Reverse<usize>, Reverse<usize>)>,
+    seen: HashSet<u64>,
+    goal: (usize, usize),
+    max_ticks: usize,
+    level_info: LevelInfo,
+    pub status: SearchStatus
+}
+
+impl HintSearch {
+    pub fn new(grid: &Grid, tick: usize, goal: (usize, usize), max_ticks: usize, level_info: &LevelInfo) -> Self {
+        let mut search = Self {
+            nodes: Vec::new(),
+            frontier: BinaryHeap::new(),
+            seen: HashSet::new(),
+            goal,
+            max_ticks,
+            level_info: level_info.clone(),
+            status: SearchStatus::Searching
+        };
+
+        search.push(SearchNode {
+            parent: 0,
+            step: None,
+            grid: grid.clone(),
+            tick,
+            ticks_used: 0,
+            activations_this_tick: 0
+        });
+        search
+    }
+
+    // Looks at up to budget more grids
+    pub fn step(&mut self, budget: usize) -> &SearchStatus {
+        for _ in 0..budget {
+            if self.status != SearchStatus::Searching { break; }
+
+            let index = if let Some((_, Reverse(index))) = self.frontier.pop() {
+                index
+            } else {
+                self.status = SearchStatus::NotFound;
+                break;
+            };
+
+            if self.nodes.len() >= MAX_SEARCH_NODES {
+                self.status = SearchStatus::GaveUp;
+                break;
+            }
+
+            let player_pos = match self.nodes[index].grid.get_entity_from_id("Player", 0) {
+                Some((pos, _)) => *pos,
+                None => continue
+            };
+
+            if player_pos == self.goal {
+                self.status = SearchStatus::Found(self.path_to(index));
+                break;
+            }
+
+            for action in self.actions_from(index) {
+                let node = &self.nodes[index];
+
+                let (ticks_used, activations_this_tick) = if action.takes_tick() {
+                    (node.ticks_used + 1, 0)
+                } else {
+                    (node.ticks_used, node.activations_this_tick + 1)
+                };
+
+                if ticks_used > self.max_ticks || activations_this_tick > MAX_ACTIVATIONS_PER_TICK { continue; }
+
+                let mut grid = node.grid.clone();
+
+                if let Some(tick) = simulate_action(&mut grid, node.tick, action, &self.level_info) {
+                    self.push(SearchNode {
+                        parent: index,
+                        step: Some((action, player_pos)),
+                        grid,
+                        tick,
+                        ticks_used,
+                        activations_this_tick
+                    });
+                }
+            }
+        }
+
+        &self.status
+    }
+
+    fn actions_from(&self, index: usize) -> Vec<Action> {
+        let mut actions: Vec<Action> = MoveDirection::ALL.iter().map(|&direction| Action::Move(direction)).collect();
+        actions.push(Action::Wait);
+
+        actions.extend(self.nodes[index].grid
+            .get_all_of_type("TimeMachine")
+            .into_iter()
+            .filter_map(|(_, time_machine)| match time_machine {
+                GridEntity::TimeMachine { id, .. } => Some(Action::Activate(*id)),
+                _ => None
+            })
+        );
+
+        actions
+    }
+
+    fn push(&mut self, node: SearchNode) {
+        if self.seen.insert(state_key(&node.grid, node.tick)) {
+            let distance = node.grid.get_entity_from_id("Player", 0).map_or(0, |(pos, _)| {
+                pos.0.abs_diff(self.goal.0) + pos.1.abs_diff(self.goal.1)
+            });
+
+            self.frontier.push((Reverse(node.ticks_used + GOAL_DISTANCE_WEIGHT * distance), Reverse(self.nodes.len())));
+            self.nodes.push(node);
+        }
+    }
+
+    fn path_to(&self, mut index: usize) -> Vec<SolutionStep> {
+        let mut path = Vec::new();
+
+        while let Some(step) = self.nodes[index].step {
+            path.push(step);
+            index = self.nodes[index].parent;
+        }
+
+        path.reverse();
+        path
+    }
+}
+
+// Grids that only differ in things that can't matter anymore count as the same. While every
+// machine is idle the player's past actions and the tick can't change what happens next.
+// Walls and the shapes of time machines never change during a search so they're left out
+fn state_key(grid: &Grid, tick: usize) -> u64 {
+    let all_idle = grid
+        .get_all_of_type("TimeMachine")
+        .into_iter()
+        .all(|(_, time_machine)| matches!(time_machine, GridEntity::TimeMachine { start_instance: TimeMachineState::Idle, .. }));
+
+    let mut hasher = DefaultHasher::new();
+
+    for (corner, grid_entity) in grid.entities_iter() {
+        corner.hash(&mut hasher);
+        hash_entity(grid_entity, all_idle, &mut hasher);
+    }
+
+    if !all_idle {
+        tick.hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
+
+fn hash_entity(grid_entity: &GridEntity, all_idle: bool, hasher: &mut DefaultHasher) {
+    std::mem::discriminant(grid_entity).hash(hasher);
+
+    match grid_entity {
+        GridEntity::Player { actions, inventory } => {
+            if !all_idle { actions.hash(hasher); }
+            inventory.hash(hasher);
+        },
+        GridEntity::PastPlayer { id, actions, inventory } => (id, actions, inventory).hash(hasher),
+        GridEntity::Box { id } | GridEntity::Key { id } | GridEntity::Lock { id } => id.hash(hasher),
+        GridEntity::Plate { id, active } => (id, active).hash(hasher),
+        GridEntity::Door { id, open, .. } => (id, open).hash(hasher),
+        GridEntity::TimeMachine { start_instance, id, grid, pushable, kind, .. } => {
+            (id, grid, pushable, kind).hash(hasher);
+            hash_time_machine_state(start_instance, hasher);
+        },
+        GridEntity::Custom(custom) => (custom.0.variant(), custom.0.serialize()).hash(hasher),
+        GridEntity::None => {}
+    }
+}
+
+// Snapshots are left out, which one a machine has follows from when it started recording
+// and the player's actions, and those get hashed whenever a machine isn't idle
+fn hash_time_machine_state(state: &TimeMachineState, hasher: &mut DefaultHasher) {
+    std::mem::discriminant(state).hash(hasher);
+
+    match state {
+        TimeMachineState::Idle => {},
+        TimeMachineState::Holding { release_tick, held } => {
+            release_tick.hash(hasher);
+
+            for held_entity in held {
+                held_entity.slots.hash(hasher);
+
+                for (index, (corner, grid_entity)) in held_entity.entities.iter() {
+                    (index, corner).hash(hasher);
+                    hash_entity(grid_entity, false, hasher);
+                }
+            }
+        },
+        TimeMachineState::Recording { start_tick, .. } => start_tick.hash(hasher),
+        TimeMachineState::Looping { start, length, next_departure, .. } => (start, length, next_departure).hash(hasher)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_with_player(width: usize, height: usize, player_pos: (usize, usize)) -> Grid {
+        let mut grid = Grid::new_sized(width, height);
+        grid.add_entity_to_pos(player_pos.0, player_pos.1, &mut GridEntityInfo { variant: "Player", ..default() });
+
+        grid
+    }
+
+    fn search(grid: &Grid, goal: (usize, usize), max_ticks: usize) -> SearchStatus {
+        let mut search = HintSearch::new(grid, 0, goal, max_ticks, &LevelInfo::default());
+
+        while search.step(100) == &SearchStatus::Searching {}
+
+        search.status
+    }
+
+    #[test]
+    fn moves_into_walls_are_refused() {
+        let mut grid = grid_with_player(2, 1, (0, 0));
+        grid.set_wall(1, 0, true);
+
+        assert_eq!(simulate_action(&mut grid, 0, Action::Move(MoveDirection::Right), &LevelInfo::default()), None);
+        assert_eq!(simulate_action(&mut grid, 0, Action::Wait, &LevelInfo::default()), Some(1));
+    }
+
+    #[test]
+    fn activating_does_not_take_a_tick() {
+        let mut grid = Grid::new_sized(3, 3);
+        grid.add_time_machine((0, 0), 3, 3, 1);
+        grid.add_entity_to_pos(1, 1, &mut GridEntityInfo { variant: "Player", ..default() });
+
+        assert_eq!(simulate_action(&mut grid, 4, Action::Activate(1), &LevelInfo::default()), Some(4));
+    }
+
+    #[test]
+    fn finds_the_shortest_way_on_an_open_grid() {
+        let grid = grid_with_player(3, 1, (0, 0));
+
+        assert_eq!(search(&grid, (2, 0), 5), SearchStatus::Found(vec![
+            (Action::Move(MoveDirection::Right), (0, 0)),
+            (Action::Move(MoveDirection::Right), (1, 0))
+        ]));
+    }
+
+    #[test]
+    fn walled_off_goals_are_not_found() {
+        let mut grid = grid_with_player(3, 1, (0, 0));
+        grid.set_wall(1, 0, true);
+
+        assert_eq!(search(&grid, (2, 0), 5), SearchStatus::NotFound);
+    }
+
+    #[test]
+    fn goals_past_the_tick_limit_are_not_found() {
+        let grid = grid_with_player(5, 1, (0, 0));
+
+        assert_eq!(search(&grid, (4, 0), 3), SearchStatus::NotFound);
+        assert!(matches!(search(&grid, (4, 0), 4), SearchStatus::Found(path) if path.len() == 4));
+    }
+
+    #[test]
+    fn idle_grids_forget_the_past_actions() {
+        let grid = grid_with_player(3, 1, (0, 0));
+        let waited = {
+            let mut grid = grid.clone();
+            simulate_action(&mut grid, 0, Action::Wait, &LevelInfo::default());
+            grid
+        };
+
+        assert_eq!(state_key(&grid, 0), state_key(&waited, 1));
+    }
+
+    #[test]
+    fn recording_grids_keep_the_tick_and_actions_apart() {
+        let mut grid = grid_with_player(4, 3, (0, 0));
+        grid.add_time_machine((2, 0), 2, 2, 1);
+
+        let snapshot = grid.clone();
+
+        if let Some((_, GridEntity::TimeMachine { start_instance, .. })) = grid.get_entity_from_id_mut("TimeMachine", 1) {
+            *start_instance = TimeMachineState::Recording { start_tick: 0, snapshot };
+        }
+
+        let mut waited = grid.clone();
+        simulate_action(&mut waited, 0, Action::Wait, &LevelInfo::default());
+
+        assert_eq!(state_key(&grid, 1), state_key(&grid.clone(), 1));
+        assert_ne!(state_key(&grid, 1), state_key(&grid, 2));
+        assert_ne!(state_key(&grid, 1), state_key(&waited, 1));
+    }
+}
@@ -0,0 +1,110 @@
+use super::*;
+
+// Idle -> Recording (first click)
+// Recording -> Looping (click with the player inside, the player goes back to start_tick)
+// Looping -> Looping (departure, next_departure moves forward by length)
+// Recording | Looping -> Idle (click without the player inside)
+// Recording | Looping -> Looping (click without the player inside when the level sends
+//     contents on their own, only the contents go back to start_tick)
+// Stasis machines only go between these two:
+// Idle -> Holding (click, the contents leave the grid)
+// Holding -> Idle (release_tick or the next click, once all the contents fit back in)
+#[derive(Default, Debug, PartialEq, Clone)]
+pub enum TimeMachineState {
+    #[default]
+    Idle,
+    Holding {
+        release_tick: Option<usize>,
+        held: Vec<HeldEntity>
+    },
+    Recording {
+        start_tick: usize,
+        snapshot: Grid
+    },
+    Looping {
+        start: usize,
+        length: usize,
+        next_departure: usize,
+        snapshot: Grid
+    }
+}
+
+// Something a stasis machine took out of the grid
+#[derive(Default, Debug, PartialEq, Clone)]
+pub struct HeldEntity {
+    // The cells of the machine it was in, relative to the machine's corner
+    pub slots: Vec<(usize, usize)>,
+    // The entity itself first, then whatever was inside of it, positions
+    // are relative to the machine's corner so it can be pushed around meanwhile
+    pub entities: Vec<(usize, ((usize, usize), GridEntity))>
+}
+
+impl TimeMachineState {
+    pub fn is_idle(&self) -> bool {
+        self == &TimeMachineState::Idle
+    }
+
+    pub fn start_tick(&self) -> Option<usize> {
+        match self {
+            TimeMachineState::Idle | TimeMachineState::Holding { .. } => None,
+            TimeMachineState::Recording { start_tick, .. } => Some(*start_tick),
+            TimeMachineState::Looping { start, .. } => Some(*start)
+        }
+    }
+
+    pub fn loop_length(&self) -> Option<usize> {
+        if let TimeMachineState::Looping { length, .. } = self { Some(*length) } else { None }
+    }
+
+    pub fn next_departure(&self) -> Option<usize> {
+        if let TimeMachineState::Looping { next_departure, .. } = self { Some(*next_departure) } else { None }
+    }
+
+    pub fn snapshot(&self) -> Option<&Grid> {
+        match self {
+            TimeMachineState::Idle | TimeMachineState::Holding { .. } => None,
+            TimeMachineState::Recording { snapshot, .. } |
+            TimeMachineState::Looping { snapshot, .. } => Some(snapshot)
+        }
+    }
+
+    pub fn snapshot_mut(&mut self) -> Option<&mut Grid> {
+        match self {
+            TimeMachineState::Idle | TimeMachineState::Holding { .. } => None,
+            TimeMachineState::Recording { snapshot, .. } |
+            TimeMachineState::Looping { snapshot, .. } => Some(snapshot)
+        }
+    }
+
+    pub fn release_tick(&self) -> Option<usize> {
+        if let TimeMachineState::Holding { release_tick, .. } = self { *release_tick } else { None }
+    }
+
+    pub fn start_recording(&mut self, tick: usize, snapshot: Grid) {
+        *self = TimeMachineState::Recording { start_tick: tick, snapshot };
+    }
+
+    pub fn stop(&mut self) {
+        *self = TimeMachineState::Idle;
+    }
+
+    // Called when the contents get sent back to the start, tick is when that happened
+    pub fn depart(&mut self, tick: usize) {
+        *self = match std::mem::take(self) {
+            TimeMachineState::Recording { start_tick, snapshot } => TimeMachineState::Looping {
+                start: start_tick,
+                length: tick - start_tick,
+                next_departure: 2 * tick - start_tick,
+                snapshot
+            },
+            TimeMachineState::Looping { start, length, next_departure, snapshot } => TimeMachineState::Looping {
+                start,
+                length,
+                next_departure: next_departure + length,
+                snapshot
+            },
+            TimeMachineState::Idle => panic!("Tried to depart with an idle time machine"),
+            TimeMachineState::Holding { .. } => panic!("Tried to depart with a stasis machine")
+        };
+    }
+}
@@ -1,10 +1,15 @@
 use super::*;
 use iyes_loopless::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 // Until I figure out how to make the setup fns only activate once
 #[derive(Resource)]
-struct LevelSetupCompleted(bool);
+pub struct LevelSetupCompleted(bool);
+
+// The player's actions from before the level got hot reloaded, they get
+// redone one per frame on the new layout
+#[derive(Resource, Default)]
+pub struct ReplayQueue(pub VecDeque<Action>);
 
 pub struct LevelSetupPlugin;
 
@@ -12,7 +17,26 @@ impl Plugin for LevelSetupPlugin {
     fn build(&self, app: &mut App) {
         app
             .insert_resource(LevelSetupCompleted(false))
-            .add_system(set_dims.run_if(level_setup_ready).before("pre_grid_setup"))
+            .insert_resource(ReplayQueue::default())
+            .add_system(reload_level_setup.before("set_dims"))
+            .add_system(
+                replay_actions
+                .run_if(replay_ready)
+                .after("queue held key")
+                .before("request queued action")
+            )
+            .add_system(set_dims.run_if(level_setup_ready).label("set_dims").before("pre_grid_setup"))
+            .add_system(
+                report_field_errors
+                .run_if(level_setup_ready)
+                .before("end_level_setup")
+            )
+            .add_system(
+                autotile_time_machine_parts
+                .run_if(level_setup_ready)
+                .after("set_dims")
+                .before("pre_grid_setup")
+            )
             .add_system_set(
                 ConditionSet::new()
                 .run_if(level_setup_ready)
@@ -27,6 +51,11 @@ impl Plugin for LevelSetupPlugin {
                 .after("pre_grid_setup")
                 .before("end_level_setup")
                 .label("grid_setup")
+            ).add_system(
+                link_active_indicators
+                .run_if(level_setup_ready)
+                .after("grid_setup")
+                .before("end_level_setup")
             ).add_system(
                 end_level_setup
                 .run_if(level_setup_ready)
@@ -43,15 +72,264 @@ fn level_setup_ready(
     ldtk_level_query.get_single().is_ok() && !level_setup_completed.0
 }
 
+pub fn level_setup_completed(level_setup_completed: Res<LevelSetupCompleted>) -> bool {
+    level_setup_completed.0
+}
+
+// Saving the LDtk file respawns the level, which needs to go through the whole setup again.
+// Only a change to the file replays the inputs, moving to another level starts it fresh
+fn reload_level_setup(
+    mut asset_events: EventReader<AssetEvent<LdtkAsset>>,
+    mut level_events: EventReader<LevelEvent>,
+    mut pending_replay: Local<Option<VecDeque<Action>>>,
+    mut level_setup_completed: ResMut<LevelSetupCompleted>,
+    mut replay_queue: ResMut<ReplayQueue>,
+    mut input_queue: ResMut<InputQueue>,
+    mut ticks: ResMut<Ticks>,
+    ldtk_query: Query<&Handle<LdtkAsset>>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    level_info: Res<LevelInfo>,
+    grid: Res<Grid>
+) {
+    for asset_event in asset_events.iter() {
+        if let AssetEvent::Modified { handle } = asset_event {
+            // The grid is still the one from before the change, so the actions are still the player's
+            if level_setup_completed.0 && ldtk_query.iter().any(|ldtk_handle| ldtk_handle == handle) {
+                *pending_replay = Some(
+                    if let Some((_, GridEntity::Player { actions, .. })) = grid.get_entity_from_id("Player", 0) {
+                        actions.iter().copied().collect()
+                    } else {
+                        VecDeque::new()
+                    }
+                );
+            }
+        }
+    }
+
+    for level_event in level_events.iter() {
+        if let (LevelEvent::Spawned(iid), true) = (level_event, level_setup_completed.0) {
+            let same_level = ldtk_query
+                .iter()
+                .filter_map(|ldtk_handle| ldtk_assets.get(ldtk_handle))
+                .flat_map(|ldtk_asset| ldtk_asset.iter_levels())
+                .any(|level| &level.iid == iid && level.identifier == level_info.identifier);
+
+            replay_queue.0 = match (pending_replay.take(), same_level) {
+                (Some(actions), true) => {
+                    info!("Level reloaded, replaying the last inputs");
+                    actions
+                },
+                _ => VecDeque::new()
+            };
+
+            input_queue.0.clear();
+            ticks.0 = 0;
+            level_setup_completed.0 = false;
+        }
+    }
+}
+
+fn replay_ready(
+    level_setup_completed: Res<LevelSetupCompleted>,
+    replay_queue: Res<ReplayQueue>,
+    input_queue: Res<InputQueue>
+) -> bool {
+    level_setup_completed.0 && !replay_queue.0.is_empty() && input_queue.0.is_empty()
+}
+
+// Moves go through the input queue like key presses, activations act like clicks
+fn replay_actions(
+    mut replay_queue: ResMut<ReplayQueue>,
+    mut input_queue: ResMut<InputQueue>,
+    mut action_requests: EventWriter<PlayerActionRequested>
+) {
+    match replay_queue.0.pop_front() {
+        Some(action @ Action::Activate(_)) => action_requests.send(PlayerActionRequested(action)),
+        Some(action) => input_queue.0.push_back(action),
+        None => {}
+    }
+}
+
 fn set_dims(
     ldtk_level: Res<Assets<LdtkLevel>>,
     ldtk_level_query: Query<&Handle<LdtkLevel>>,
-    mut dims: ResMut<Dims>
+    mut dims: ResMut<Dims>,
+    mut level_info: ResMut<LevelInfo>,
+    mut level_stats: ResMut<LevelStats>
 ) {
     let level = &ldtk_level.get(ldtk_level_query.single()).unwrap().level;
+    let _span = info_span!("setup", identifier = %level.identifier).entered();
 
     dims.x = (level.px_wid / 256) as usize;
     dims.y = (level.px_hei / 256) as usize;
+
+    let mut errors: Vec<FieldError> = Vec::new();
+
+    let goal = optional_field(level.point_field("Goal"), &mut errors).and_then(|point| {
+        if point.x < 0 || point.y < 0 || point.x as usize >= dims.x || point.y as usize >= dims.y {
+            errors.push(FieldError::InvalidValue { identifier: "Goal".to_string(), value: point.to_string() });
+            None
+        } else {
+            Some((point.x as usize, dims.y - 1 - point.y as usize))
+        }
+    });
+
+    *level_info = LevelInfo {
+        identifier: level.identifier.clone(),
+        name: field_or(level.string_field("Name"), level.identifier.clone(), &mut errors),
+        goal,
+        par_moves: optional_field(level.count_field("ParMoves"), &mut errors),
+        par_time_travels: optional_field(level.count_field("ParTimeTravels"), &mut errors),
+        hint: optional_field(level.string_field("Hint"), &mut errors),
+        duplication_rule: field_or(level.enum_field("DuplicationRule"), DuplicationRule::default(), &mut errors),
+        send_without_player: field_or(level.bool_field("SendWithoutPlayer"), false, &mut errors)
+    };
+
+    *level_stats = LevelStats::default();
+
+    for error in errors {
+        warn!("Error loading level {}: {}", level.identifier, error);
+    }
+}
+
+// Bad fields don't stop the level from loading, they just get reported
+fn report_field_errors(
+    ldtk_level: Res<Assets<LdtkLevel>>,
+    ldtk_level_query: Query<&Handle<LdtkLevel>>,
+    entity_instances: Query<(&EntityInstance, &GridCoords)>
+) {
+    let level = &ldtk_level.get(ldtk_level_query.single()).unwrap().level;
+    let _span = info_span!("setup", identifier = %level.identifier).entered();
+
+    for (entity_instance, coords) in entity_instances.iter() {
+        for error in entity_field_errors(entity_instance) {
+            warn!(
+                "Error loading level {}: {} at ({}, {}): {}",
+                level.identifier,
+                entity_instance.identifier,
+                coords.x,
+                coords.y,
+                error
+            );
+        }
+    }
+}
+
+// Parts painted without a type get the one matching their neighbours, a side is
+// inside if there is a part next to it and otherwise open or a wall depending on
+// whether the part is marked as an opening
+fn autotile_time_machine_parts(
+    mut time_machine_parts_query: Query<(
+        &mut TimeMachinePartType,
+        &mut TextureAtlasSprite,
+        &mut Handle<TextureAtlas>,
+        &TimeMachineCell,
+        &GridCoords
+    )>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    asset_server: Res<AssetServer>,
+    dims: Res<Dims>
+) {
+    let mut cell_grid: Vec<Vec<Option<(TimeMachineCell, TimeMachinePartType)>>> = vec![vec![None; dims.y]; dims.x];
+
+    for (part_type, _, _, cell, coords) in time_machine_parts_query.iter() {
+        cell_grid[coords.x as usize][coords.y as usize] = Some((*cell, *part_type));
+    }
+
+    let neighbour = |x: usize, y: usize, direction: MoveDirection| direction
+        .get_changed_pos(&(x, y), dims.x, dims.y)
+        .and_then(|(x, y)| cell_grid[x][y].map(|cell| ((x, y), cell)));
+
+    // Hand placed parts keep their edges, so they can still be used to keep two
+    // time machines next to each other apart
+    let mut edge_grid: Vec<Vec<Option<TimeMachineEdges>>> = vec![vec![None; dims.y]; dims.x];
+
+    for x in 0..dims.x {
+        for y in 0..dims.y {
+            let (cell, part_type) = if let Some(cell) = cell_grid[x][y] { cell } else { continue; };
+
+            if !cell.autotile {
+                edge_grid[x][y] = Some(part_type.edges());
+                continue;
+            }
+
+            let edge = |direction: MoveDirection| match neighbour(x, y, direction) {
+                Some((_, (other_cell, other_part_type))) if other_cell.autotile
+                    || other_part_type.edges().get(direction.opposite()) == Edge::Inside => Edge::Inside,
+                _ => Edge::Wall
+            };
+
+            edge_grid[x][y] = Some(TimeMachineEdges::new(
+                edge(MoveDirection::Up),
+                edge(MoveDirection::Down),
+                edge(MoveDirection::Left),
+                edge(MoveDirection::Right)
+            ));
+        }
+    }
+
+    // An opening in a corner only opens the side the opening runs along, so a row of
+    // openings doesn't also open the walls at its ends. If that doesn't pick a side
+    // (a single part time machine) the first outside side gets opened
+    let outside_grid = edge_grid.clone();
+
+    for x in 0..dims.x {
+        for y in 0..dims.y {
+            let cell = if let Some((cell, _)) = cell_grid[x][y] { cell } else { continue; };
+            if !cell.autotile || !cell.opening { continue; }
+
+            let edges = outside_grid[x][y].unwrap();
+            let outside: Vec<MoveDirection> = MoveDirection::ALL
+                .into_iter()
+                .filter(|side| edges.get(*side) != Edge::Inside)
+                .collect();
+
+            let mut open: Vec<MoveDirection> = outside.iter().copied().filter(|side| {
+                outside.len() == 1 || side.perpendicular().into_iter().any(|along| {
+                    match neighbour(x, y, along) {
+                        Some(((x, y), (other_cell, _))) if !other_cell.autotile => outside_grid[x][y].unwrap().get(*side) == Edge::Open,
+                        Some(((x, y), (other_cell, _))) => other_cell.opening && outside_grid[x][y].unwrap().get(*side) != Edge::Inside,
+                        None => false
+                    }
+                })
+            }).collect();
+
+            if open.is_empty() {
+                open.extend(outside.first());
+            }
+
+            for side in open {
+                edge_grid[x][y].as_mut().unwrap().set(side, Edge::Open);
+            }
+        }
+    }
+
+    let texture_atlas = tileset_atlas(&mut texture_atlases, &asset_server);
+
+    for (mut part_type, mut sprite, mut atlas, cell, coords) in time_machine_parts_query.iter_mut() {
+        if !cell.autotile { continue; }
+
+        let (x, y) = (coords.x as usize, coords.y as usize);
+        let edges = edge_grid[x][y].unwrap();
+
+        *part_type = TimeMachinePartType::from_edges(edges);
+
+        // A wall that runs into an opening of the same time machine gets a tangent end
+        'tangent: for wall_side in MoveDirection::ALL.into_iter().filter(|side| edges.get(*side) == Edge::Wall) {
+            for towards in wall_side.perpendicular() {
+                let opening_next = edges.get(towards) == Edge::Inside && neighbour(x, y, towards)
+                    .is_some_and(|((x, y), _)| edge_grid[x][y].unwrap().get(wall_side) == Edge::Open);
+
+                if let (true, Some(tangent)) = (opening_next, part_type.with_tangent(wall_side, towards)) {
+                    *part_type = tangent;
+                    break 'tangent;
+                }
+            }
+        }
+
+        sprite.index = part_type.tile_index();
+        *atlas = texture_atlas.clone();
+    }
 }
 
 fn setup_time_machine_parts(
@@ -86,16 +364,27 @@ fn setup_time_machine_parts(
         }
     });
 
-    let mut corner_map: HashMap<usize, GridCoords> = HashMap::new();
-
-    for (mut grid_entity, _, coords) in sorted_time_machine_parts {
+    for (_, _, coords) in sorted_time_machine_parts.iter() {
         if time_machine_part_grid[coords.x as usize][coords.y as usize].unwrap().0 == 0 {
             expand_connections(coords.x as usize, coords.y as usize, &mut time_machine_part_grid, id);
-            corner_map.insert(id, *coords);
 
             id += 1;
         }
+    }
 
+    // The corner is the bottom left of the bounding box, which for non-rectangular
+    // time machines doesn't have to be one of the parts
+    let mut corner_map: HashMap<usize, GridCoords> = HashMap::new();
+
+    for (_, _, coords) in sorted_time_machine_parts.iter() {
+        let part_id = time_machine_part_grid[coords.x as usize][coords.y as usize].unwrap().0;
+        let corner = corner_map.entry(part_id).or_insert(**coords);
+
+        corner.x = corner.x.min(coords.x);
+        corner.y = corner.y.min(coords.y);
+    }
+
+    for (mut grid_entity, _, coords) in sorted_time_machine_parts {
         grid_entity.id = time_machine_part_grid[coords.x as usize][coords.y as usize].unwrap().0;
         let corner = corner_map.get(&grid_entity.id).unwrap();
         grid_entity.pos = (
@@ -106,12 +395,12 @@ fn setup_time_machine_parts(
 }
 
 fn expand_connections(x: usize, y: usize, grid: &mut Vec<Vec<Option<(usize, TimeMachinePartType)>>>, expanding_id: usize) {
-    // println!("[{}]: Expanding connections at ({}, {})", expanding_id, x, y);
+    trace!("[{}]: Expanding connections at ({}, {})", expanding_id, x, y);
 
     if let Some((id, part_type)) = grid[x][y] { if id == 0 {
         grid[x][y].as_mut().unwrap().0 = expanding_id;
 
-        // println!("[{}]: {:?}", expanding_id, grid[x][y]);
+        trace!("[{}]: {:?}", expanding_id, grid[x][y]);
 
         if x > 0 && grid[ x-1 ][ y ].is_some() && part_type.fits_on_left(&grid[ x-1 ][ y ].unwrap().1) {
             expand_connections(x - 1, y, grid, expanding_id);
@@ -156,37 +445,143 @@ fn resize_level(
 
 fn setup_grid(
     dims: Res<Dims>,
+    ldtk_level: Res<Assets<LdtkLevel>>,
+    ldtk_level_query: Query<&Handle<LdtkLevel>>,
     mut grid: ResMut<Grid>,
-    mut grid_entities: Query<(&mut GridEntityInfo, &GridCoords, Option<&TimeMachinePartType>)>,
+    mut grid_entities: Query<(
+        &mut GridEntityInfo,
+        &GridCoords,
+        Option<&TimeMachinePartType>,
+        Option<&PushableTimeMachine>,
+        Option<&TimeMachineKind>,
+        Option<&LdtkIid>,
+        Option<&DoorPlates>,
+        Option<&CustomEntity>
+    )>,
 ) {
     *grid = Grid::new_sized(dims.x, dims.y);
 
-    for (mut grid_entity_info, coords, opt_part_type) in grid_entities.iter_mut() {
+    let level = &ldtk_level.get(ldtk_level_query.single()).unwrap().level;
+    let _span = info_span!("setup", identifier = %level.identifier).entered();
+
+    // LDtk stores the rows top to bottom, GridCoords go bottom to top
+    let collisions = level.layer_instances
+        .iter()
+        .flatten()
+        .find(|layer_instance| layer_instance.identifier == "Collisions");
+
+    if let Some(collisions) = collisions {
+        for (i, value) in collisions.int_grid_csv.iter().enumerate() {
+            let x = i % collisions.c_wid as usize;
+            let y = dims.y - 1 - i / collisions.c_wid as usize;
+
+            grid.set_wall(x, y, *value != 0);
+        }
+    }
+
+    // Doors get linked after everything is added since their plates might not have ids yet
+    let mut plate_ids: HashMap<String, usize> = HashMap::new();
+    let mut doors: Vec<(usize, Vec<String>)> = Vec::new();
+
+    for (mut grid_entity_info, coords, opt_part_type, opt_pushable, opt_kind, opt_iid, opt_door_plates, opt_custom) in grid_entities.iter_mut() {
         if grid_entity_info.id == 0 { grid_entity_info.id = grid.num_entities() };
 
-        grid.add_entity_to_pos(
-            coords.x as usize,
-            coords.y as usize,
-            &mut grid_entity_info
-        );
+        if let (Some(iid), "Plate") = (opt_iid, grid_entity_info.variant) {
+            plate_ids.insert(iid.0.clone(), grid_entity_info.id);
+        }
 
-        let (corner, entity) = grid.get_entity_mut(&grid_entity_info).unwrap();
+        if let Some(door_plates) = opt_door_plates {
+            doors.push((grid_entity_info.id, door_plates.0.clone()));
+        }
 
-        if grid_entity_info.pos == (0, 0) {
-            *corner = (
+        if let Some(custom) = opt_custom {
+            let mut custom = custom.clone();
+            custom.0.set_id(grid_entity_info.id);
+
+            grid.add_custom_entity_to_pos(
+                coords.x as usize,
+                coords.y as usize,
+                custom,
+                &mut grid_entity_info
+            );
+        } else {
+            grid.add_entity_to_pos(
                 coords.x as usize,
-                coords.y as usize
+                coords.y as usize,
+                &mut grid_entity_info
             );
         }
 
+        let (corner, entity) = grid.get_entity_mut(&grid_entity_info).unwrap();
+
+        *corner = (
+            coords.x as usize - grid_entity_info.pos.0,
+            coords.y as usize - grid_entity_info.pos.1
+        );
+
         entity.try_add_part_to_grid(
             grid_entity_info.pos.0,
             grid_entity_info.pos.1,
-            opt_part_type
+            opt_part_type,
+            opt_pushable,
+            opt_kind
         );
     }
 
-    // println!("{}", *grid);
+    for (door_id, plate_iids) in doors {
+        grid.link_door(
+            door_id,
+            plate_iids.iter().filter_map(|iid| plate_ids.get(iid).copied()).collect()
+        );
+    }
+
+    grid.update_plates();
+
+    debug!("Set up the grid of {} with {} entities", level.identifier, grid.num_entities());
+    trace!("{}", *grid);
+}
+
+// Indicators point at a machine through their Machine field, or sit on one of its parts.
+// Machines without one get theirs later from update_active_indicators
+fn link_active_indicators(
+    mut indicators_query: Query<(&mut ActiveIndicator, &GridCoords, &Transform)>,
+    parts_query: Query<(&GridEntityInfo, &LdtkIid, &GridCoords)>,
+    ldtk_level: Res<Assets<LdtkLevel>>,
+    ldtk_level_query: Query<&Handle<LdtkLevel>>,
+    grid: Res<Grid>
+) {
+    let level = &ldtk_level.get(ldtk_level_query.single()).unwrap().level;
+    let _span = info_span!("setup", identifier = %level.identifier).entered();
+
+    let parts: Vec<(usize, &LdtkIid, &GridCoords)> = parts_query
+        .iter()
+        .filter(|(grid_entity_info, ..)| grid_entity_info.variant == "TimeMachine")
+        .map(|(grid_entity_info, iid, coords)| (grid_entity_info.id, iid, coords))
+        .collect();
+
+    for (mut indicator, coords, transform) in indicators_query.iter_mut() {
+        let opt_id = match &indicator.machine_iid {
+            Some(machine_iid) => parts.iter().find(|(_, iid, _)| &iid.0 == machine_iid),
+            None => parts.iter().find(|(.., part_coords)| *part_coords == coords)
+        }.map(|(id, ..)| *id);
+
+        let corner = opt_id.and_then(|id| grid.get_entity_from_id("TimeMachine", id)).map(|(corner, _)| *corner);
+
+        if let (Some(id), Some(corner)) = (opt_id, corner) {
+            indicator.machine_id = Some(id);
+            indicator.offset = transform.translation.truncate() - Vec2::new(
+                (corner.0 * TILE_SIZE) as f32,
+                (corner.1 * TILE_SIZE) as f32
+            );
+        } else {
+            warn!(
+                "Error loading level {}: ActiveIndicator at ({}, {}) isn't linked to a time machine",
+                level.identifier,
+                coords.x,
+                coords.y
+            );
+        }
+    }
 }
 
 fn end_level_setup(mut level_setup_completed: ResMut<LevelSetupCompleted>) {
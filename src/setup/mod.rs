@@ -3,13 +3,16 @@ pub use super::*;
 pub mod level_setup_plugin;
 pub use level_setup_plugin::*;
 
+// Relative to the assets folder
+pub const LDTK_FILE: &str = "test_level.ldtk";
+
 pub fn setup(
     mut commands: Commands, 
     asset_server: Res<AssetServer>
 ) {
     let camera = Camera2dBundle::default();
     let ldtk_handle = LdtkWorldBundle {
-        ldtk_handle: asset_server.load("test_level.ldtk"),
+        ldtk_handle: asset_server.load(LDTK_FILE),
         ..Default::default()
     };
 